@@ -1,45 +1,349 @@
 extern crate snafu;
 
-use snafu::{OptionExt, ResultExt, Snafu};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::num::ParseIntError;
 use std::path::{Path, PathBuf};
 
 fn main() {
-    println!("Part1: {:?}", part1());
-    println!("Part2: {:?}", part2());
+    let (map, args): (Vec<_>, Vec<_>) = env::args().skip(1).partition(|arg| arg == "--map");
+    let layout = match Layout::from_args(args.into_iter()) {
+        Ok(layout) => layout,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    println!("Part1: {:?}", part1(&layout));
+    println!("Part2: {:?}", part2(&layout));
+    if !map.is_empty() {
+        match seat_map(&layout) {
+            Ok(seats) => print_analysis(&seats),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
 }
 
-fn part1() -> Result<usize> {
-    read_lines("../i")?
-        .flatten()
-        .map(|l| bitstring(&l))
-        .flatten()
+fn part1(layout: &Layout) -> Result<usize> {
+    layout
+        .decode_all(&read_passes("../i")?)?
+        .iter()
+        .map(|seat| layout.seat_id(seat))
         .max()
         .context(NoValidLines {})
 }
 
-fn part2() -> Result<usize> {
-    let ids: Vec<usize> = read_lines("../i")?
-        .flatten()
-        .map(|l| bitstring(&l))
-        .flatten()
-        .collect();
-    Ok((*ids.iter().min().context(NoValidLines {})? as i32
-        ..=*ids.iter().max().context(NoValidLines {})? as i32)
-        .chain(ids.iter().map(|x| -(*x as i32)))
-        .sum::<i32>() as usize)
+fn part2(layout: &Layout) -> Result<usize> {
+    your_seat(&seat_map(layout)?)
 }
 
-fn bitstring(s: &str) -> Result<usize> {
-    usize::from_str_radix(
-        s.replace(&['F', 'L'][..], "0")
-            .replace(&['B', 'R'][..], "1")
-            .as_str(),
-        2,
-    )
-    .context(ParseInt {})
+fn your_seat(seats: &SeatMap) -> Result<usize> {
+    match seats.your_seats().as_slice() {
+        [seat] => Ok(seats.layout.seat_id(seat)),
+        [] => NoFreeSeat {}.fail(),
+        candidates => AmbiguousSeat {
+            candidates: candidates.len(),
+        }
+        .fail(),
+    }
+}
+
+fn seat_map(layout: &Layout) -> Result<SeatMap<'_>> {
+    Ok(SeatMap::new(
+        layout,
+        &layout.decode_all(&read_passes("../i")?)?,
+    ))
+}
+
+fn print_analysis(seats: &SeatMap) {
+    print!("{}", seats);
+    let ids = |list: &[Seat]| {
+        list.iter()
+            .map(|seat| seats.layout.seat_id(seat))
+            .collect::<Vec<_>>()
+    };
+    println!("Empty seats: {:?}", ids(&seats.empty_seats()));
+    println!(
+        "Duplicate passes: {:?}",
+        seats
+            .duplicates()
+            .iter()
+            .map(|(seat, count)| (seats.layout.seat_id(seat), *count))
+            .collect::<Vec<_>>()
+    );
+    println!("Unused front rows: {:?}", seats.front_unused());
+    println!("Unused back rows: {:?}", seats.back_unused());
+    println!("Your seat: {:?}", ids(&seats.your_seats()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Seat {
+    row: usize,
+    col: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Layout {
+    row_bits: usize,
+    col_bits: usize,
+    row_lower: char,
+    row_upper: char,
+    col_lower: char,
+    col_upper: char,
+    // seat id = row * row_factor + col
+    row_factor: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout::new(7, 3)
+    }
+}
+
+impl Layout {
+    fn new(row_bits: usize, col_bits: usize) -> Layout {
+        Layout {
+            row_bits,
+            col_bits,
+            row_lower: 'F',
+            row_upper: 'B',
+            col_lower: 'L',
+            col_upper: 'R',
+            row_factor: 1 << col_bits,
+        }
+    }
+
+    fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Layout> {
+        let mut layout = Layout::default();
+        let mut row_factor = None;
+        while let Some(flag) = args.next() {
+            let value = args.next().context(MissingValue { flag: flag.clone() })?;
+            match flag.as_str() {
+                "--rows" => layout.row_bits = value.parse().context(ParseInt {})?,
+                "--cols" => layout.col_bits = value.parse().context(ParseInt {})?,
+                "--row-letters" => {
+                    let (lower, upper) = letter_pair(&value)?;
+                    layout.row_lower = lower;
+                    layout.row_upper = upper;
+                }
+                "--col-letters" => {
+                    let (lower, upper) = letter_pair(&value)?;
+                    layout.col_lower = lower;
+                    layout.col_upper = upper;
+                }
+                "--row-factor" => row_factor = Some(value.parse().context(ParseInt {})?),
+                _ => return UnknownFlag { flag }.fail(),
+            }
+        }
+        layout.row_factor = row_factor.unwrap_or(1 << layout.col_bits.min(63));
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    fn validate(&self) -> Result<()> {
+        // checked one at a time so their sum cannot overflow
+        let max_bits = usize::BITS as usize;
+        ensure!(
+            self.row_bits > 0
+                && self.row_bits < max_bits
+                && self.col_bits < max_bits
+                && self.pass_len() < max_bits,
+            InvalidLayout {
+                reason: format!(
+                    "row bits must be at least 1 and row plus column bits below {}",
+                    usize::BITS
+                ),
+            }
+        );
+        ensure!(
+            self.row_lower != self.row_upper && self.col_lower != self.col_upper,
+            InvalidLayout {
+                reason: "lower and upper letters must differ",
+            }
+        );
+        ensure!(
+            self.row_factor > self.max_col(),
+            InvalidLayout {
+                reason: "row factor must be larger than the highest column",
+            }
+        );
+        let max_row = (1usize << self.row_bits) - 1;
+        ensure!(
+            max_row
+                .checked_mul(self.row_factor)
+                .and_then(|id| id.checked_add(self.max_col()))
+                .is_some(),
+            InvalidLayout {
+                reason: "the highest seat id does not fit in a usize",
+            }
+        );
+        Ok(())
+    }
+
+    fn pass_len(&self) -> usize {
+        self.row_bits + self.col_bits
+    }
+
+    fn max_col(&self) -> usize {
+        (1 << self.col_bits) - 1
+    }
+
+    fn seat_id(&self, seat: &Seat) -> usize {
+        seat.row * self.row_factor + seat.col
+    }
+
+    fn decode(&self, pass: &str) -> Result<Seat> {
+        let chars = pass.chars().collect::<Vec<_>>();
+        ensure!(
+            chars.len() == self.pass_len(),
+            WrongLength {
+                pass,
+                expected: self.pass_len(),
+                found: chars.len(),
+            }
+        );
+        let (rows, cols) = chars.split_at(self.row_bits);
+        Ok(Seat {
+            row: bits(pass, rows, self.row_lower, self.row_upper)?,
+            col: bits(pass, cols, self.col_lower, self.col_upper)?,
+        })
+    }
+
+    // Every pass must be as long as the first, which must suit the layout
+    fn decode_all(&self, passes: &[String]) -> Result<Vec<Seat>> {
+        let expected = passes.first().map_or(0, |pass| pass.chars().count());
+        for (i, pass) in passes.iter().enumerate() {
+            let found = pass.chars().count();
+            ensure!(
+                found == expected,
+                InconsistentLength {
+                    line: i + 1,
+                    expected,
+                    found,
+                }
+            );
+        }
+        passes.iter().map(|pass| self.decode(pass)).collect()
+    }
+}
+
+struct SeatMap<'a> {
+    layout: &'a Layout,
+    // number of boarding passes decoded to each seat
+    taken: BTreeMap<Seat, usize>,
+}
+
+impl<'a> SeatMap<'a> {
+    fn new(layout: &'a Layout, seats: &[Seat]) -> SeatMap<'a> {
+        let mut taken = BTreeMap::new();
+        for seat in seats {
+            *taken.entry(*seat).or_insert(0) += 1;
+        }
+        SeatMap { layout, taken }
+    }
+
+    fn rows(&self) -> usize {
+        1 << self.layout.row_bits
+    }
+
+    fn is_taken(&self, seat: &Seat) -> bool {
+        self.taken.contains_key(seat)
+    }
+
+    fn front_unused(&self) -> std::ops::Range<usize> {
+        0..self
+            .taken
+            .keys()
+            .next()
+            .map_or(self.rows(), |seat| seat.row)
+    }
+
+    fn back_unused(&self) -> std::ops::Range<usize> {
+        self.taken
+            .keys()
+            .next_back()
+            .map_or(self.rows(), |seat| seat.row + 1)..self.rows()
+    }
+
+    // Every free seat between the first and last occupied rows
+    fn empty_seats(&self) -> Vec<Seat> {
+        let first = self.front_unused().end;
+        let last = self.back_unused().start;
+        (first..last)
+            .flat_map(|row| (0..=self.layout.max_col()).map(move |col| Seat { row, col }))
+            .filter(|seat| !self.is_taken(seat))
+            .collect()
+    }
+
+    fn duplicates(&self) -> Vec<(Seat, usize)> {
+        self.taken
+            .iter()
+            .filter(|(_, &count)| count > 1)
+            .map(|(seat, &count)| (*seat, count))
+            .collect()
+    }
+
+    // Free seats whose ids on either side are both taken
+    fn your_seats(&self) -> Vec<Seat> {
+        let ids = self
+            .taken
+            .keys()
+            .map(|seat| self.layout.seat_id(seat))
+            .collect::<HashSet<_>>();
+        self.empty_seats()
+            .into_iter()
+            .filter(|seat| {
+                let id = self.layout.seat_id(seat);
+                id > 0 && ids.contains(&(id - 1)) && ids.contains(&(id + 1))
+            })
+            .collect()
+    }
+}
+
+impl<'a> fmt::Display for SeatMap<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yours = self.your_seats();
+        let width = (self.rows() - 1).to_string().len();
+        for row in 0..self.rows() {
+            write!(f, "{:>width$} ", row, width = width)?;
+            for col in 0..=self.layout.max_col() {
+                let seat = Seat { row, col };
+                let c = if yours.contains(&seat) {
+                    'X'
+                } else if self.is_taken(&seat) {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn bits(pass: &str, chars: &[char], lower: char, upper: char) -> Result<usize> {
+    chars.iter().try_fold(0, |acc, &c| match c {
+        _ if c == lower => Ok(acc << 1),
+        _ if c == upper => Ok((acc << 1) | 1),
+        _ => InvalidChar { pass, c }.fail(),
+    })
+}
+
+fn letter_pair(s: &str) -> Result<(char, char)> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(lower), Some(upper), None) => Ok((lower, upper)),
+        _ => InvalidLayout {
+            reason: "letters must be given as a pair such as FB",
+        }
+        .fail(),
+    }
 }
 
 #[derive(Debug, Snafu)]
@@ -49,14 +353,55 @@ enum Error {
         filename: PathBuf,
         source: std::io::Error,
     },
+    #[snafu(display("Could not read line: {}", source))]
+    ReadLine { source: std::io::Error },
     #[snafu(display("Could not find any valid lines"))]
     NoValidLines {},
+    #[snafu(display("Could not find a free seat between two taken ones"))]
+    NoFreeSeat {},
+    #[snafu(display("Found {} candidate seats, expected exactly one", candidates))]
+    AmbiguousSeat { candidates: usize },
     #[snafu(display("Could not open parse int: {}", source))]
     ParseInt { source: ParseIntError },
+    #[snafu(display("Invalid character {:?} in boarding pass {}", c, pass))]
+    InvalidChar { pass: String, c: char },
+    #[snafu(display("Boarding pass {} has length {}, expected {}", pass, found, expected))]
+    WrongLength {
+        pass: String,
+        expected: usize,
+        found: usize,
+    },
+    #[snafu(display(
+        "Boarding pass on line {} has length {}, expected {} like the first pass",
+        line,
+        found,
+        expected
+    ))]
+    InconsistentLength {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[snafu(display("Invalid layout: {}", reason))]
+    InvalidLayout { reason: String },
+    #[snafu(display("Missing value for {}", flag))]
+    MissingValue { flag: String },
+    #[snafu(display("Unknown flag {}", flag))]
+    UnknownFlag { flag: String },
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+fn read_passes<P>(filename: P) -> Result<Vec<String>>
+where
+    P: AsRef<Path>,
+{
+    read_lines(filename)?
+        .map(|line| line.context(ReadLine {}))
+        .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .collect()
+}
+
 // The output is wrapped in a Result to allow matching on errors
 // Returns an Iterator to the Reader of the lines of the file.
 fn read_lines<P>(filename: P) -> Result<io::Lines<io::BufReader<File>>>
@@ -68,3 +413,134 @@ where
     })?;
     Ok(io::BufReader::new(file).lines())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(args: &[&str]) -> Result<Layout> {
+        Layout::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn decodes_the_example() {
+        let layout = Layout::default();
+        let seat = layout.decode("FBFBBFFRLR").unwrap();
+        assert_eq!(seat, Seat { row: 44, col: 5 });
+        assert_eq!(layout.seat_id(&seat), 357);
+    }
+
+    #[test]
+    fn custom_layout() {
+        let layout = layout(&["--rows", "2", "--cols", "1", "--row-letters", "UD"]).unwrap();
+        assert_eq!(layout.pass_len(), 3);
+        assert_eq!(layout.decode("DUR").unwrap(), Seat { row: 2, col: 1 });
+        assert_eq!(layout.seat_id(&Seat { row: 2, col: 1 }), 5);
+    }
+
+    #[test]
+    fn rejects_bad_layouts() {
+        let invalid = |args: &[&str]| matches!(layout(args), Err(Error::InvalidLayout { .. }));
+        assert!(invalid(&["--rows", "0"]));
+        assert!(invalid(&["--rows", "18446744073709551615", "--cols", "1"]));
+        assert!(invalid(&["--cols", "18446744073709551615"]));
+        assert!(invalid(&["--rows", "40", "--cols", "24"]));
+        assert!(invalid(&["--row-letters", "FF"]));
+        assert!(invalid(&["--col-letters", "LRX"]));
+        assert!(invalid(&["--row-factor", "7"]));
+        assert!(layout(&["--row-factor", "8"]).is_ok());
+        assert!(matches!(
+            layout(&["--rows"]),
+            Err(Error::MissingValue { .. })
+        ));
+        assert!(matches!(
+            layout(&["--seats", "1"]),
+            Err(Error::UnknownFlag { .. })
+        ));
+    }
+
+    #[test]
+    fn passes_must_share_a_length() {
+        let passes = ["FBFBBFFRLR", "BFFFBBFRR"]
+            .iter()
+            .map(|pass| pass.to_string())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            Layout::default().decode_all(&passes),
+            Err(Error::InconsistentLength {
+                line: 2,
+                expected: 10,
+                found: 9,
+            })
+        ));
+    }
+
+    fn map_of<'a>(layout: &'a Layout, seats: &[(usize, usize)]) -> SeatMap<'a> {
+        let seats = seats
+            .iter()
+            .map(|&(row, col)| Seat { row, col })
+            .collect::<Vec<_>>();
+        SeatMap::new(layout, &seats)
+    }
+
+    #[test]
+    fn seat_map_analysis() {
+        let layout = Layout::new(2, 2);
+        let seats = map_of(
+            &layout,
+            &[
+                (1, 0),
+                (1, 1),
+                (1, 3),
+                (2, 0),
+                (2, 0),
+                (2, 1),
+                (2, 2),
+                (2, 3),
+            ],
+        );
+        assert_eq!(seats.empty_seats(), vec![Seat { row: 1, col: 2 }]);
+        assert_eq!(seats.duplicates(), vec![(Seat { row: 2, col: 0 }, 2)]);
+        assert_eq!(seats.front_unused(), 0..1);
+        assert_eq!(seats.back_unused(), 3..4);
+        assert_eq!(your_seat(&seats).unwrap(), 6);
+        assert_eq!(seats.to_string(), "0 ....\n1 ##X#\n2 ####\n3 ....\n");
+    }
+
+    #[test]
+    fn empty_seat_map() {
+        let layout = Layout::new(2, 2);
+        let seats = map_of(&layout, &[]);
+        assert_eq!(seats.front_unused(), 0..4);
+        assert_eq!(seats.back_unused(), 4..4);
+        assert!(seats.empty_seats().is_empty());
+        assert!(matches!(your_seat(&seats), Err(Error::NoFreeSeat {})));
+    }
+
+    #[test]
+    fn several_missing_seats() {
+        let layout = Layout::new(2, 2);
+        let full = map_of(&layout, &[(1, 0), (1, 1), (1, 2), (1, 3)]);
+        assert!(matches!(your_seat(&full), Err(Error::NoFreeSeat {})));
+        // ids 4, 6, 8 and 10 leave 5, 7 and 9 between two taken seats
+        let gaps = map_of(&layout, &[(1, 0), (1, 2), (2, 0), (2, 2)]);
+        assert_eq!(gaps.empty_seats().len(), 4);
+        assert!(matches!(
+            your_seat(&gaps),
+            Err(Error::AmbiguousSeat { candidates: 3 })
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_passes() {
+        let layout = Layout::default();
+        assert!(matches!(
+            layout.decode("FBFBBFFRL"),
+            Err(Error::WrongLength { .. })
+        ));
+        assert!(matches!(
+            layout.decode("FBFBBFFRLX"),
+            Err(Error::InvalidChar { c: 'X', .. })
+        ));
+    }
+}
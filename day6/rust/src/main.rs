@@ -1,51 +1,465 @@
-extern crate itertools;
+extern crate serde;
+extern crate serde_json;
 extern crate snafu;
 
-use itertools::Itertools;
+use serde::Serialize;
 use snafu::{OptionExt, ResultExt, Snafu};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
 use std::fs;
+use std::iter::FromIterator;
+use std::num::ParseIntError;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
 
 fn main() {
-    println!("Part1: {:?}", part1());
-    println!("Part2: {:?}", part2());
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("bench") => {
+            match args
+                .get(1)
+                .map_or(Ok(100_000), |n| n.parse().context(ParseInt {}))
+            {
+                Ok(groups) => bench(groups),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        Some("stats") => {
+            match report(args.get(1).map_or("table", String::as_str)) {
+                Ok(report) => print!("{}", report),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        _ => {}
+    }
+    let queries = args
+        .iter()
+        .map(|arg| arg.parse::<Query>())
+        .collect::<Result<Vec<_>>>();
+    match queries {
+        Ok(queries) if queries.is_empty() => {
+            println!("Part1: {:?}", part1());
+            println!("Part2: {:?}", part2());
+        }
+        Ok(queries) => {
+            for query in queries {
+                match run_query(&query) {
+                    Ok(answer) => println!("{}: {}", query, answer),
+                    Err(e) => eprintln!("{}: {}", query, e),
+                }
+            }
+        }
+        Err(e) => eprintln!("{}", e),
+    }
 }
 
 fn part1() -> Result<usize> {
-    Ok(read_responses("../i")?
-        .iter()
-        .map(|responses| {
-            responses
-                .iter()
-                .map(|response| response.chars())
-                .flatten()
-                .unique()
-                .count()
-        })
-        .sum())
+    total(&read_groups("../i")?, &Query::Union)
 }
 
 fn part2() -> Result<usize> {
-    Ok(read_responses("../i")?
-        .iter()
-        .map(intersect_count)
-        .collect::<Result<Vec<_>, _>>()?
+    total(&read_groups("../i")?, &Query::Intersection)
+}
+
+fn run_query(query: &Query) -> Result<Answer> {
+    let groups = read_groups("../i")?;
+    match query {
+        Query::Frequency => Ok(Answer::Counts(frequency(&groups))),
+        _ => Ok(Answer::Total(total(&groups, query)?)),
+    }
+}
+
+fn frequency(groups: &[Group]) -> BTreeMap<char, usize> {
+    let mut counts = BTreeMap::new();
+    for group in groups {
+        for (question, count) in group.frequency() {
+            *counts.entry(question).or_insert(0) += count;
+        }
+    }
+    counts
+}
+
+fn report(format: &str) -> Result<String> {
+    let stats = Stats::new(&read_groups("../i")?);
+    match format {
+        "table" => Ok(stats.table()),
+        "json" => Ok(serde_json::to_string_pretty(&stats).context(Json {})? + "\n"),
+        _ => UnknownFormat { format }.fail(),
+    }
+}
+
+fn total(groups: &[Group], query: &Query) -> Result<usize> {
+    groups
         .iter()
-        .sum())
+        .map(|group| Ok(query.apply(group)?.len()))
+        .sum()
+}
+
+#[derive(Debug)]
+enum Query {
+    Union,
+    Intersection,
+    // questions answered by exactly one person in the group; for two people
+    // this is the usual symmetric difference
+    SymmetricDifference,
+    AtLeast(usize),
+    Frequency,
+}
+
+#[derive(Debug)]
+enum Answer {
+    Total(usize),
+    Counts(BTreeMap<char, usize>),
+}
+
+impl Query {
+    // The questions selected by this query for a single group
+    fn apply(&self, group: &Group) -> Result<AnswerSet> {
+        let mut people = group.people.iter();
+        Ok(match self {
+            Query::Union => match people.next() {
+                Some(first) => people.fold(first.clone(), |acc, set| acc.union(set)),
+                None => AnswerSet::Bits(0),
+            },
+            Query::Intersection => {
+                let first = people.next().context(NoValidLines {})?;
+                people.fold(first.clone(), |acc, set| acc.intersection(set))
+            }
+            Query::SymmetricDifference => {
+                let first = match people.next() {
+                    Some(first) => first,
+                    None => return Ok(AnswerSet::Bits(0)),
+                };
+                // both start as the first person's kind so a hash set group
+                // never needs converting
+                let (once, _) = people.fold((first.clone(), first.empty()), |(once, more), set| {
+                    let more = more.union(&once.intersection(set));
+                    (once.union(set).difference(&more), more)
+                });
+                once
+            }
+            Query::AtLeast(k) => group
+                .frequency()
+                .into_iter()
+                .filter(|(_, count)| count >= k)
+                .map(|(question, _)| question)
+                .collect(),
+            Query::Frequency => group.frequency().keys().cloned().collect(),
+        })
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Query> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("union"), None) => Ok(Query::Union),
+            (Some("intersection"), None) => Ok(Query::Intersection),
+            (Some("symdiff"), None) => Ok(Query::SymmetricDifference),
+            (Some("atleast"), Some(k)) => Ok(Query::AtLeast(k.parse().context(ParseInt {})?)),
+            (Some("frequency"), None) => Ok(Query::Frequency),
+            _ => UnknownQuery { query: s }.fail(),
+        }
+    }
+}
+
+impl std::fmt::Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Query::Union => write!(f, "union"),
+            Query::Intersection => write!(f, "intersection"),
+            Query::SymmetricDifference => write!(f, "symdiff"),
+            Query::AtLeast(k) => write!(f, "atleast:{}", k),
+            Query::Frequency => write!(f, "frequency"),
+        }
+    }
+}
+
+impl std::fmt::Display for Answer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Answer::Total(total) => write!(f, "{}", total),
+            Answer::Counts(counts) => write!(
+                f,
+                "{}",
+                counts
+                    .iter()
+                    .map(|(question, count)| format!("{}={}", question, count))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Group {
+    people: Vec<AnswerSet>,
+}
+
+impl Group {
+    fn frequency(&self) -> BTreeMap<char, usize> {
+        let mut counts = BTreeMap::new();
+        for question in self.people.iter().flat_map(AnswerSet::chars) {
+            *counts.entry(question).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GroupStats {
+    size: usize,
+    answers: BTreeMap<char, usize>,
+    // everyone gave the same answers, which a group of one always has
+    unanimous: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    groups: Vec<GroupStats>,
+    answers: BTreeMap<char, usize>,
+    most_common: Vec<char>,
+    least_common: Vec<char>,
+    // group size -> number of groups of that size
+    size_histogram: BTreeMap<usize, usize>,
+}
+
+impl Stats {
+    fn new(groups: &[Group]) -> Stats {
+        let answers = frequency(groups);
+        let with_count = |count: Option<&usize>| {
+            answers
+                .iter()
+                .filter(|(_, c)| Some(*c) == count)
+                .map(|(question, _)| *question)
+                .collect()
+        };
+        let most_common = with_count(answers.values().max());
+        let least_common = with_count(answers.values().min());
+        let mut size_histogram = BTreeMap::new();
+        for group in groups {
+            *size_histogram.entry(group.people.len()).or_insert(0) += 1;
+        }
+        Stats {
+            groups: groups
+                .iter()
+                .map(|group| GroupStats {
+                    size: group.people.len(),
+                    answers: group.frequency(),
+                    unanimous: group.people.windows(2).all(|pair| pair[0] == pair[1]),
+                })
+                .collect(),
+            answers,
+            most_common,
+            least_common,
+            size_histogram,
+        }
+    }
+
+    fn table(&self) -> String {
+        let mut out = format!("{:>6} {:>5} {:>9}  answers\n", "group", "size", "unanimous");
+        for (i, group) in self.groups.iter().enumerate() {
+            out += &format!(
+                "{:>6} {:>5} {:>9}  {}\n",
+                i + 1,
+                group.size,
+                if group.unanimous { "yes" } else { "no" },
+                Answer::Counts(group.answers.clone())
+            );
+        }
+        out += &format!("\n{:>8} {:>6}\n", "question", "count");
+        for (question, count) in &self.answers {
+            out += &format!("{:>8} {:>6}\n", question, count);
+        }
+        out += &format!("\nmost common: {:?}\n", self.most_common);
+        out += &format!("least common: {:?}\n", self.least_common);
+        out += &format!(
+            "unanimous groups: {}\n",
+            self.groups.iter().filter(|group| group.unanimous).count()
+        );
+        out += &format!("\n{:>4} {:>6}\n", "size", "groups");
+        let widest = self.size_histogram.values().max().cloned().unwrap_or(1);
+        for (size, count) in &self.size_histogram {
+            out += &format!(
+                "{:>4} {:>6} {}\n",
+                size,
+                count,
+                "#".repeat((count * 50).div_ceil(widest))
+            );
+        }
+        out
+    }
 }
 
-fn hashset(data: &String) -> HashSet<&u8> {
-    data.as_bytes().iter().collect::<HashSet<_>>()
+// Answers drawn only from 'a'..='z' fit in a bitmask, anything else falls
+// back to a hash set. Mixed operands are combined as hash sets.
+#[derive(Debug, Clone, PartialEq)]
+enum AnswerSet {
+    Bits(u32),
+    Hash(HashSet<char>),
 }
 
-fn intersect_count(sets: &Vec<String>) -> Result<usize> {
-    let mut iter = sets.iter().map(hashset);
-    Ok(iter
-        .next()
-        .map(|set| iter.fold(set, |set1, set2| &set1 & &set2))
-        .context(NoValidLines {})?
-        .len())
+impl AnswerSet {
+    fn hashed(answers: &str) -> AnswerSet {
+        AnswerSet::Hash(answers.chars().collect())
+    }
+
+    // An empty set of the same kind
+    fn empty(&self) -> AnswerSet {
+        match self {
+            AnswerSet::Bits(_) => AnswerSet::Bits(0),
+            AnswerSet::Hash(_) => AnswerSet::Hash(HashSet::new()),
+        }
+    }
+
+    fn bit(c: char) -> Option<u32> {
+        if c.is_ascii_lowercase() {
+            Some(1 << (c as u32 - 'a' as u32))
+        } else {
+            None
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            AnswerSet::Bits(bits) => bits.count_ones() as usize,
+            AnswerSet::Hash(set) => set.len(),
+        }
+    }
+
+    fn chars(&self) -> Vec<char> {
+        match self {
+            AnswerSet::Bits(bits) => (0..26)
+                .filter(|i| bits & (1 << i) != 0)
+                .map(|i| (b'a' + i as u8) as char)
+                .collect(),
+            AnswerSet::Hash(set) => set.iter().cloned().collect(),
+        }
+    }
+
+    fn to_hash(&self) -> HashSet<char> {
+        match self {
+            AnswerSet::Bits(_) => self.chars().into_iter().collect(),
+            AnswerSet::Hash(set) => set.clone(),
+        }
+    }
+
+    fn combine(
+        &self,
+        other: &AnswerSet,
+        bits: fn(u32, u32) -> u32,
+        hash: fn(&HashSet<char>, &HashSet<char>) -> HashSet<char>,
+    ) -> AnswerSet {
+        match (self, other) {
+            (AnswerSet::Bits(a), AnswerSet::Bits(b)) => AnswerSet::Bits(bits(*a, *b)),
+            (AnswerSet::Hash(a), AnswerSet::Hash(b)) => AnswerSet::Hash(hash(a, b)),
+            _ => AnswerSet::Hash(hash(&self.to_hash(), &other.to_hash())),
+        }
+    }
+
+    fn union(&self, other: &AnswerSet) -> AnswerSet {
+        self.combine(other, |a, b| a | b, |a, b| a | b)
+    }
+
+    fn intersection(&self, other: &AnswerSet) -> AnswerSet {
+        self.combine(other, |a, b| a & b, |a, b| a & b)
+    }
+
+    fn difference(&self, other: &AnswerSet) -> AnswerSet {
+        self.combine(other, |a, b| a & !b, |a, b| a - b)
+    }
+}
+
+impl FromIterator<char> for AnswerSet {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> AnswerSet {
+        let chars = iter.into_iter().collect::<Vec<_>>();
+        match chars
+            .iter()
+            .try_fold(0, |bits, &c| Some(bits | AnswerSet::bit(c)?))
+        {
+            Some(bits) => AnswerSet::Bits(bits),
+            None => AnswerSet::Hash(chars.into_iter().collect()),
+        }
+    }
+}
+
+impl FromStr for AnswerSet {
+    type Err = Error;
+    fn from_str(answers: &str) -> Result<AnswerSet> {
+        Ok(answers.chars().collect())
+    }
+}
+
+// Timed runs of each query, after one untimed warmup run
+const BENCH_RUNS: usize = 5;
+
+// Times the bitset and hash set representations on a generated survey,
+// reporting the fastest run
+fn bench(groups: usize) {
+    let survey = synthetic_survey(groups);
+    let bits = to_groups(&survey, |person| person.chars().collect());
+    let hashed = to_groups(&survey, AnswerSet::hashed);
+    for query in &[
+        Query::Union,
+        Query::Intersection,
+        Query::SymmetricDifference,
+    ] {
+        for (name, groups) in &[("bitset", &bits), ("hashset", &hashed)] {
+            let result = total(groups, query);
+            let fastest = (0..BENCH_RUNS)
+                .map(|_| {
+                    let start = Instant::now();
+                    let _ = total(groups, query);
+                    start.elapsed()
+                })
+                .min()
+                .unwrap_or_default();
+            println!(
+                "{:<12} {:<8} {:?} in {:?}",
+                query.to_string(),
+                name,
+                result,
+                fastest
+            );
+        }
+    }
+}
+
+fn to_groups(survey: &[Vec<String>], set: fn(&str) -> AnswerSet) -> Vec<Group> {
+    survey
+        .iter()
+        .map(|people| Group {
+            people: people.iter().map(|person| set(person)).collect(),
+        })
+        .collect()
+}
+
+// Deterministic xorshift so runs are comparable
+fn synthetic_survey(groups: usize) -> Vec<Vec<String>> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move |bound: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % bound
+    };
+    (0..groups)
+        .map(|_| {
+            (0..=next(5))
+                .map(|_| {
+                    (0..26u8)
+                        .filter(|_| next(3) == 0)
+                        .map(|i| (b'a' + i) as char)
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
 }
 
 #[derive(Debug, Snafu)]
@@ -57,21 +471,164 @@ enum Error {
     },
     #[snafu(display("Could not find any valid lines"))]
     NoValidLines {},
+    #[snafu(display("Could not open parse int: {}", source))]
+    ParseInt { source: ParseIntError },
+    #[snafu(display(
+        "Unknown query {}, expected union, intersection, symdiff (answered by exactly one \
+         person), atleast:<k> or frequency",
+        query
+    ))]
+    UnknownQuery { query: String },
+    #[snafu(display("Unknown stats format {}, expected table or json", format))]
+    UnknownFormat { format: String },
+    #[snafu(display("Could not serialize stats: {}", source))]
+    Json { source: serde_json::Error },
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
-// The output is wrapped in a Result to allow matching on errors
-// Returns an Iterator to the Reader of the lines of the file.
-fn read_responses<P>(filename: P) -> Result<Vec<Vec<String>>>
+// Groups are separated by blank lines, one person per line
+fn read_groups<P>(filename: P) -> Result<Vec<Group>>
 where
     P: AsRef<Path>,
 {
-    Ok(fs::read_to_string(&filename)
+    let mut groups = vec![];
+    let mut people = vec![];
+    for line in fs::read_to_string(&filename)
         .context(OpenFile {
             filename: filename.as_ref(),
         })?
-        .split("\r\n\r\n")
-        .map(|s| s.to_string().split("\r\n").map(|s| s.to_string()).collect())
-        .collect())
+        .lines()
+    {
+        if line.trim().is_empty() {
+            if !people.is_empty() {
+                groups.push(Group { people });
+                people = vec![];
+            }
+        } else {
+            people.push(line.trim().parse()?);
+        }
+    }
+    if !people.is_empty() {
+        groups.push(Group { people });
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(people: &[&str]) -> Group {
+        Group {
+            people: people
+                .iter()
+                .map(|person| person.parse().unwrap())
+                .collect(),
+        }
+    }
+
+    fn answers(query: &str, group: &Group) -> String {
+        let mut chars = query
+            .parse::<Query>()
+            .unwrap()
+            .apply(group)
+            .unwrap()
+            .chars();
+        chars.sort();
+        chars.into_iter().collect()
+    }
+
+    #[test]
+    fn queries_on_a_group_of_three() {
+        let group = group(&["abc", "bcd", "ce"]);
+        assert_eq!(answers("union", &group), "abcde");
+        assert_eq!(answers("intersection", &group), "c");
+        // b is answered twice, so it is not in the symmetric difference
+        assert_eq!(answers("symdiff", &group), "ade");
+        assert_eq!(answers("atleast:2", &group), "bc");
+        assert_eq!(answers("atleast:4", &group), "");
+        assert_eq!(answers("frequency", &group), "abcde");
+        assert_eq!(
+            Answer::Counts(frequency(&[group])).to_string(),
+            "a=1 b=2 c=3 d=1 e=1"
+        );
+    }
+
+    #[test]
+    fn symdiff_of_two_people() {
+        assert_eq!(answers("symdiff", &group(&["abc", "bcd"])), "ad");
+    }
+
+    #[test]
+    fn bitset_and_hash_totals_agree() {
+        let survey = synthetic_survey(200);
+        let bits = to_groups(&survey, |person| person.chars().collect());
+        let hashed = to_groups(&survey, AnswerSet::hashed);
+        for query in &["union", "intersection", "symdiff", "atleast:2", "frequency"] {
+            let query = query.parse::<Query>().unwrap();
+            assert_eq!(
+                total(&bits, &query).unwrap(),
+                total(&hashed, &query).unwrap(),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn hash_groups_stay_hash_sets() {
+        let group = Group {
+            people: vec![AnswerSet::hashed("ab"), AnswerSet::hashed("bc")],
+        };
+        for query in &[Query::Union, Query::SymmetricDifference] {
+            assert!(matches!(query.apply(&group), Ok(AnswerSet::Hash(_))));
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let groups = [
+            group(&["ab", "ab"]),
+            group(&["a", "b", "ab"]),
+            group(&["cd"]),
+            group(&["a", "b"]),
+        ];
+        let stats = Stats::new(&groups);
+        assert_eq!(
+            stats.size_histogram.into_iter().collect::<Vec<_>>(),
+            vec![(1, 1), (2, 2), (3, 1)]
+        );
+        assert_eq!(Answer::Counts(stats.answers).to_string(), "a=5 b=5 c=1 d=1");
+        assert_eq!(stats.most_common, vec!['a', 'b']);
+        assert_eq!(stats.least_common, vec!['c', 'd']);
+        assert_eq!(
+            stats
+                .groups
+                .iter()
+                .map(|group| group.unanimous)
+                .collect::<Vec<_>>(),
+            vec![true, false, true, false]
+        );
+        assert!(Stats::new(&groups)
+            .table()
+            .contains("unanimous groups: 2\n"));
+    }
+
+    #[test]
+    fn parse_queries() {
+        for query in &["union", "intersection", "symdiff", "atleast:3", "frequency"] {
+            assert_eq!(query.parse::<Query>().unwrap().to_string(), *query);
+        }
+        for query in &["atleast", "union:1", "xor", ""] {
+            assert!(matches!(
+                query.parse::<Query>(),
+                Err(Error::UnknownQuery { .. })
+            ));
+        }
+        assert!(matches!(
+            "atleast:x".parse::<Query>(),
+            Err(Error::ParseInt { .. })
+        ));
+    }
 }
@@ -0,0 +1,260 @@
+// A one-to-one map that can be looked up from either side. Inserting a pair
+// replaces any existing pair sharing its left or right value, so both sides
+// always describe the same set of pairs.
+
+use std::borrow::Borrow;
+use std::collections::hash_map;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter::FromIterator;
+
+#[derive(Debug, Clone)]
+pub struct BiMap<S, T> {
+    forward: HashMap<S, T>,
+    reverse: HashMap<T, S>,
+}
+
+impl<S, T> Default for BiMap<S, T>
+where
+    S: Eq + Hash + Clone,
+    T: Eq + Hash + Clone,
+{
+    fn default() -> BiMap<S, T> {
+        BiMap::new()
+    }
+}
+
+impl<S, T> BiMap<S, T>
+where
+    S: Eq + Hash + Clone,
+    T: Eq + Hash + Clone,
+{
+    pub fn new() -> BiMap<S, T> {
+        BiMap {
+            forward: HashMap::new(),
+            reverse: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    // Returns the pairs that were displaced to keep the map one-to-one
+    pub fn insert(&mut self, s: S, t: T) -> Vec<(S, T)> {
+        let mut removed = vec![];
+        removed.extend(self.remove_by_left(&s));
+        removed.extend(self.remove_by_right(&t));
+        self.forward.insert(s.clone(), t.clone());
+        self.reverse.insert(t, s);
+        removed
+    }
+
+    pub fn remove_by_left<K>(&mut self, s: &K) -> Option<(S, T)>
+    where
+        S: Borrow<K>,
+        K: Eq + Hash + ?Sized,
+    {
+        let (s, t) = self.forward.remove_entry(s)?;
+        self.reverse.remove(&t);
+        Some((s, t))
+    }
+
+    pub fn remove_by_right<K>(&mut self, t: &K) -> Option<(S, T)>
+    where
+        T: Borrow<K>,
+        K: Eq + Hash + ?Sized,
+    {
+        let (t, s) = self.reverse.remove_entry(t)?;
+        self.forward.remove(&s);
+        Some((s, t))
+    }
+
+    #[inline]
+    pub fn get_by_left<K>(&self, s: &K) -> Option<&T>
+    where
+        S: Borrow<K>,
+        K: Eq + Hash + ?Sized,
+    {
+        self.forward.get(s)
+    }
+
+    #[inline]
+    pub fn get_by_right<K>(&self, t: &K) -> Option<&S>
+    where
+        T: Borrow<K>,
+        K: Eq + Hash + ?Sized,
+    {
+        self.reverse.get(t)
+    }
+
+    pub fn contains_left<K>(&self, s: &K) -> bool
+    where
+        S: Borrow<K>,
+        K: Eq + Hash + ?Sized,
+    {
+        self.forward.contains_key(s)
+    }
+
+    pub fn contains_right<K>(&self, t: &K) -> bool
+    where
+        T: Borrow<K>,
+        K: Eq + Hash + ?Sized,
+    {
+        self.reverse.contains_key(t)
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, S, T> {
+        self.forward.iter()
+    }
+
+    pub fn left_values(&self) -> hash_map::Keys<'_, S, T> {
+        self.forward.keys()
+    }
+
+    pub fn right_values(&self) -> hash_map::Keys<'_, T, S> {
+        self.reverse.keys()
+    }
+}
+
+impl<S, T> FromIterator<(S, T)> for BiMap<S, T>
+where
+    S: Eq + Hash + Clone,
+    T: Eq + Hash + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = (S, T)>>(iter: I) -> BiMap<S, T> {
+        let mut map = BiMap::new();
+        for (s, t) in iter {
+            map.insert(s, t);
+        }
+        map
+    }
+}
+
+impl<S, T> IntoIterator for BiMap<S, T> {
+    type Item = (S, T);
+    type IntoIter = hash_map::IntoIter<S, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.forward.into_iter()
+    }
+}
+
+impl<'a, S, T> IntoIterator for &'a BiMap<S, T> {
+    type Item = (&'a S, &'a T);
+    type IntoIter = hash_map::Iter<'a, S, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.forward.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BiMap;
+    use proptest::prelude::*;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Insert(String, String),
+        RemoveLeft(String),
+        RemoveRight(String),
+    }
+
+    // Small alphabets so operations collide often
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            ("[a-e]", "[v-z]").prop_map(|(s, t)| Op::Insert(s, t)),
+            "[a-e]".prop_map(Op::RemoveLeft),
+            "[v-z]".prop_map(Op::RemoveRight),
+        ]
+    }
+
+    fn apply(map: &mut BiMap<String, String>, model: &mut Vec<(String, String)>, op: Op) {
+        match op {
+            Op::Insert(s, t) => {
+                model.retain(|(ms, mt)| *ms != s && *mt != t);
+                model.push((s.clone(), t.clone()));
+                map.insert(s, t);
+            }
+            Op::RemoveLeft(s) => {
+                let expected = model.iter().position(|(ms, _)| *ms == s);
+                let expected = expected.map(|i| model.remove(i));
+                assert_eq!(map.remove_by_left(&s), expected);
+            }
+            Op::RemoveRight(t) => {
+                let expected = model.iter().position(|(_, mt)| *mt == t);
+                let expected = expected.map(|i| model.remove(i));
+                assert_eq!(map.remove_by_right(t.as_str()), expected);
+            }
+        }
+    }
+
+    fn sorted(pairs: &BiMap<String, String>) -> Vec<(String, String)> {
+        let mut pairs = pairs
+            .iter()
+            .map(|(s, t)| (s.clone(), t.clone()))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        pairs
+    }
+
+    proptest! {
+        #[test]
+        fn sides_stay_consistent(ops in prop::collection::vec(op(), 0..64)) {
+            let mut map = BiMap::new();
+            let mut model = vec![];
+            for op in ops {
+                apply(&mut map, &mut model, op);
+                prop_assert_eq!(map.len(), model.len());
+                prop_assert_eq!(map.forward.len(), map.reverse.len());
+                for (s, t) in &map {
+                    prop_assert_eq!(map.get_by_right(t), Some(s));
+                    prop_assert_eq!(map.get_by_left(s), Some(t));
+                }
+            }
+            prop_assert_eq!(map.is_empty(), model.is_empty());
+            for (s, t) in &model {
+                prop_assert!(map.contains_left(s));
+                prop_assert!(map.contains_right(t));
+            }
+            let mut lefts = map.left_values().cloned().collect::<Vec<_>>();
+            let mut rights = map.right_values().cloned().collect::<Vec<_>>();
+            lefts.sort();
+            rights.sort();
+            model.sort();
+            prop_assert_eq!(lefts, model.iter().map(|(s, _)| s.clone()).collect::<Vec<_>>());
+            let mut model_rights = model.iter().map(|(_, t)| t.clone()).collect::<Vec<_>>();
+            model_rights.sort();
+            prop_assert_eq!(rights, model_rights);
+            prop_assert_eq!(sorted(&map), model.clone());
+            let mut owned = map.into_iter().collect::<Vec<_>>();
+            owned.sort();
+            prop_assert_eq!(owned, model);
+        }
+
+        #[test]
+        fn insert_replaces_both_sides(s in "[a-c]", t in "[x-z]", u in "[x-z]") {
+            let mut map = BiMap::new();
+            map.insert(s.clone(), t.clone());
+            map.insert(s.clone(), u.clone());
+            prop_assert_eq!(map.get_by_left(&s), Some(&u));
+            prop_assert_eq!(map.contains_right(&t), t == u);
+            prop_assert_eq!(map.len(), 1);
+        }
+
+        #[test]
+        fn from_iter_matches_inserts(pairs in prop::collection::vec(("[a-e]", "[v-z]"), 0..32)) {
+            let collected = pairs.iter().cloned().collect::<BiMap<String, String>>();
+            let mut inserted = BiMap::new();
+            for (s, t) in pairs {
+                inserted.insert(s, t);
+            }
+            prop_assert_eq!(sorted(&collected), sorted(&inserted));
+        }
+    }
+}
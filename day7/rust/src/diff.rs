@@ -0,0 +1,183 @@
+use error::*;
+use petgraph::visit::EdgeRef;
+use rules::BagRules;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+pub struct RuleDiff<'a> {
+    pub added: Vec<&'a str>,
+    pub removed: Vec<&'a str>,
+    // (outer, inner, old count, new count) for every edge that differs
+    pub changed: Vec<(&'a str, &'a str, Option<usize>, Option<usize>)>,
+}
+
+pub struct AnswerDiff {
+    pub colour: String,
+    pub ancestors: (Result<usize>, Result<usize>),
+    pub contained: (Result<usize>, Result<usize>),
+}
+
+pub fn diff<'a>(old: &'a BagRules, new: &'a BagRules) -> RuleDiff<'a> {
+    let old_colours = colours(old);
+    let new_colours = colours(new);
+    let old_edges = edges(old);
+    let new_edges = edges(new);
+    let changed = old_edges
+        .keys()
+        .chain(new_edges.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|&(outer, inner)| {
+            let before = old_edges.get(&(outer, inner)).cloned();
+            let after = new_edges.get(&(outer, inner)).cloned();
+            if before == after {
+                None
+            } else {
+                Some((outer, inner, before, after))
+            }
+        })
+        .collect();
+    RuleDiff {
+        added: new_colours.difference(&old_colours).cloned().collect(),
+        removed: old_colours.difference(&new_colours).cloned().collect(),
+        changed,
+    }
+}
+
+pub fn diff_answers(old: &BagRules, new: &BagRules, colour: &str) -> AnswerDiff {
+    let ancestors = |rules: &BagRules| rules.containers(colour).map(|c| c.len());
+    AnswerDiff {
+        colour: colour.to_string(),
+        ancestors: (ancestors(old), ancestors(new)),
+        contained: (old.contained_count(colour), new.contained_count(colour)),
+    }
+}
+
+fn colours(rules: &BagRules) -> BTreeSet<&str> {
+    let graph = rules.graph();
+    graph
+        .node_indices()
+        .map(|index| graph[index].as_str())
+        .collect()
+}
+
+fn edges(rules: &BagRules) -> BTreeMap<(&str, &str), usize> {
+    let graph = rules.graph();
+    graph
+        .edge_references()
+        .map(|edge| {
+            (
+                (graph[edge.source()].as_str(), graph[edge.target()].as_str()),
+                *edge.weight(),
+            )
+        })
+        .collect()
+}
+
+impl<'a> fmt::Display for RuleDiff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for colour in &self.added {
+            writeln!(f, "+ {}", colour)?;
+        }
+        for colour in &self.removed {
+            writeln!(f, "- {}", colour)?;
+        }
+        let count = |n: &Option<usize>| n.map_or("-".to_string(), |n| n.to_string());
+        for (outer, inner, before, after) in &self.changed {
+            writeln!(
+                f,
+                "~ {} -> {}: {} => {}",
+                outer,
+                inner,
+                count(before),
+                count(after)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AnswerDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let answer = |r: &Result<usize>| match r {
+            Ok(n) => n.to_string(),
+            Err(e) => format!("({})", e),
+        };
+        writeln!(
+            f,
+            "{} ancestors: {} => {}",
+            self.colour,
+            answer(&self.ancestors.0),
+            answer(&self.ancestors.1)
+        )?;
+        writeln!(
+            f,
+            "{} contains: {} => {}",
+            self.colour,
+            answer(&self.contained.0),
+            answer(&self.contained.1)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, diff_answers};
+    use rules::{BagRules, EXAMPLE};
+
+    #[test]
+    fn diffs_rules_and_answers() {
+        let old = BagRules::parse(EXAMPLE.iter().map(|line| line.to_string())).unwrap();
+        // drops dark orange, adds pale teal and changes one count
+        let new = BagRules::parse(
+            EXAMPLE
+                .iter()
+                .filter(|line| !line.starts_with("dark orange"))
+                .map(|line| match *line {
+                    "shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags." => {
+                        "shiny gold bags contain 1 dark olive bag, 3 vibrant plum bags."
+                    }
+                    "vibrant plum bags contain 5 faded blue bags, 6 dotted black bags." => {
+                        "vibrant plum bags contain 5 faded blue bags, 6 dotted black bags, \
+                         1 pale teal bag."
+                    }
+                    line => line,
+                })
+                .chain(vec!["pale teal bags contain no other bags."])
+                .map(String::from),
+        )
+        .unwrap();
+        new.validate().unwrap();
+        let rules = diff(&old, &new);
+        assert_eq!(rules.added, vec!["pale teal"]);
+        assert_eq!(rules.removed, vec!["dark orange"]);
+        assert_eq!(
+            rules.changed,
+            vec![
+                ("dark orange", "bright white", Some(3), None),
+                ("dark orange", "muted yellow", Some(4), None),
+                ("shiny gold", "vibrant plum", Some(2), Some(3)),
+                ("vibrant plum", "pale teal", None, Some(1)),
+            ]
+        );
+        assert_eq!(
+            rules.to_string(),
+            "+ pale teal\n\
+             - dark orange\n\
+             ~ dark orange -> bright white: 3 => -\n\
+             ~ dark orange -> muted yellow: 4 => -\n\
+             ~ shiny gold -> vibrant plum: 2 => 3\n\
+             ~ vibrant plum -> pale teal: - => 1\n"
+        );
+        // shiny gold now holds 1 * (1 + 7) + 3 * (1 + 12) bags
+        assert_eq!(
+            diff_answers(&old, &new, "shiny gold").to_string(),
+            "shiny gold ancestors: 4 => 3\nshiny gold contains: 32 => 47\n"
+        );
+        assert_eq!(
+            diff_answers(&old, &new, "pale teal").to_string(),
+            "pale teal ancestors: (No bag colour named pale teal) => 5\n\
+             pale teal contains: (No bag colour named pale teal) => 0\n"
+        );
+    }
+}
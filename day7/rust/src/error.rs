@@ -0,0 +1,55 @@
+use petgraph::graph::NodeIndex;
+use snafu::Snafu;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+use validate::Problem;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Could not open file {}: {}", filename.display(), source))]
+    OpenFile {
+        filename: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Could not read line: {}", source))]
+    ReadLine { source: std::io::Error },
+    #[snafu(display(
+        "Syntax error on line {} column {}: expected {}",
+        line,
+        column,
+        expected
+    ))]
+    Syntax {
+        line: usize,
+        column: usize,
+        expected: String,
+    },
+    #[snafu(display("Could not parse int: {}", source))]
+    ParseInt { source: ParseIntError },
+    #[snafu(display("No bag colour named {}", colour))]
+    UnknownColour { colour: String },
+    #[snafu(display(
+        "Invalid bag rules: {}",
+        problems
+            .iter()
+            .map(Problem::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    ))]
+    InvalidRules { problems: Vec<Problem> },
+    #[snafu(display("Bag rules contain a cycle through {}", colour))]
+    Cycle { colour: String },
+    #[snafu(display("Number of bags inside {} overflows", colour))]
+    Overflow { colour: String },
+    #[snafu(display("Could not serialize to JSON: {}", source))]
+    Json { source: serde_json::Error },
+    #[snafu(display("Unknown command {}", command))]
+    UnknownCommand { command: String },
+    #[snafu(display("Unknown or incomplete option {}", option))]
+    BadOption { option: String },
+    #[snafu(display("No bag colour at node {:?}", index))]
+    UnknownNode { index: NodeIndex },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
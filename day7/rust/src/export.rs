@@ -0,0 +1,179 @@
+use error::*;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::{Bfs, EdgeRef, Reversed};
+use rules::BagRules;
+use snafu::ResultExt;
+use std::collections::{BTreeMap, HashSet};
+
+pub enum Scope {
+    All,
+    // colours reachable from, or able to reach, the given colour
+    From(String),
+    To(String),
+}
+
+impl BagRules {
+    pub fn subgraph(&self, scope: &Scope) -> Result<HashSet<NodeIndex>> {
+        let graph = self.graph();
+        let mut nodes = HashSet::new();
+        match scope {
+            Scope::All => nodes.extend(graph.node_indices()),
+            Scope::From(colour) => {
+                let mut bfs = Bfs::new(graph, self.index(colour)?);
+                while let Some(index) = bfs.next(graph) {
+                    nodes.insert(index);
+                }
+            }
+            Scope::To(colour) => {
+                let mut bfs = Bfs::new(Reversed(graph), self.index(colour)?);
+                while let Some(index) = bfs.next(Reversed(graph)) {
+                    nodes.insert(index);
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    pub fn to_dot(&self, nodes: &HashSet<NodeIndex>, highlight: Option<&str>) -> String {
+        let graph = self.graph();
+        let quote = |index: NodeIndex| format!("\"{}\"", graph[index].replace('"', "\\\""));
+        let mut out = String::from("digraph bags {\n");
+        for index in self.sorted(nodes) {
+            if Some(graph[index].as_str()) == highlight {
+                out += &format!("    {} [style=filled, fillcolor=gold];\n", quote(index));
+            } else {
+                out += &format!("    {};\n", quote(index));
+            }
+        }
+        for index in self.sorted(nodes) {
+            for edge in graph.edges(index) {
+                if nodes.contains(&edge.target()) {
+                    out += &format!(
+                        "    {} -> {} [label={}];\n",
+                        quote(index),
+                        quote(edge.target()),
+                        edge.weight()
+                    );
+                }
+            }
+        }
+        out + "}\n"
+    }
+
+    // {"colour": [{"colour": "inner", "count": n}, ...], ...}
+    pub fn to_json(&self, nodes: &HashSet<NodeIndex>) -> Result<String> {
+        let graph = self.graph();
+        let adjacency = self
+            .sorted(nodes)
+            .into_iter()
+            .map(|index| {
+                let mut contents = graph
+                    .edges(index)
+                    .filter(|edge| nodes.contains(&edge.target()))
+                    .map(|edge| {
+                        serde_json::json!({
+                            "colour": graph[edge.target()],
+                            "count": edge.weight(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                contents.reverse();
+                (graph[index].as_str(), contents)
+            })
+            .collect::<BTreeMap<_, _>>();
+        serde_json::to_string_pretty(&adjacency).context(Json {})
+    }
+
+    fn sorted(&self, nodes: &HashSet<NodeIndex>) -> Vec<NodeIndex> {
+        let mut sorted = nodes.iter().cloned().collect::<Vec<_>>();
+        sorted.sort_by_key(|&index| &self.graph()[index]);
+        sorted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scope;
+    use rules::{BagRules, EXAMPLE};
+
+    fn example() -> BagRules {
+        BagRules::parse(EXAMPLE.iter().map(|line| line.to_string())).unwrap()
+    }
+
+    fn names(rules: &BagRules, scope: Scope) -> Vec<&str> {
+        let graph = rules.graph();
+        let mut names = rules
+            .subgraph(&scope)
+            .unwrap()
+            .into_iter()
+            .map(|index| graph[index].as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn subgraph_scopes() {
+        let rules = example();
+        assert_eq!(names(&rules, Scope::All).len(), 9);
+        assert_eq!(
+            names(&rules, Scope::From("shiny gold".to_string())),
+            vec![
+                "dark olive",
+                "dotted black",
+                "faded blue",
+                "shiny gold",
+                "vibrant plum"
+            ]
+        );
+        assert_eq!(
+            names(&rules, Scope::To("shiny gold".to_string())),
+            vec![
+                "bright white",
+                "dark orange",
+                "light red",
+                "muted yellow",
+                "shiny gold"
+            ]
+        );
+        assert!(rules.subgraph(&Scope::From("no such".to_string())).is_err());
+    }
+
+    #[test]
+    fn dot_highlights_and_labels_edges() {
+        let rules = example();
+        let nodes = rules
+            .subgraph(&Scope::From("dark olive".to_string()))
+            .unwrap();
+        assert_eq!(
+            rules.to_dot(&nodes, Some("dark olive")),
+            "digraph bags {\n\
+             \x20   \"dark olive\" [style=filled, fillcolor=gold];\n\
+             \x20   \"dotted black\";\n\
+             \x20   \"faded blue\";\n\
+             \x20   \"dark olive\" -> \"dotted black\" [label=4];\n\
+             \x20   \"dark olive\" -> \"faded blue\" [label=3];\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn json_from_a_colour() {
+        let rules = example();
+        let nodes = rules
+            .subgraph(&Scope::From("dark olive".to_string()))
+            .unwrap();
+        let json = rules.to_json(&nodes).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({
+                "dark olive": [
+                    {"colour": "faded blue", "count": 3},
+                    {"colour": "dotted black", "count": 4},
+                ],
+                "dotted black": [],
+                "faded blue": [],
+            })
+        );
+    }
+}
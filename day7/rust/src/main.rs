@@ -1,230 +1,232 @@
-extern crate itertools;
 extern crate petgraph;
+#[cfg(test)]
+extern crate proptest;
+extern crate serde_json;
 extern crate snafu;
 
-use itertools::Itertools;
-use petgraph::algo::has_path_connecting;
-use petgraph::graph::DiGraph;
-use petgraph::graph::NodeIndex;
-use snafu::{OptionExt, ResultExt, Snafu};
-use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::num::ParseIntError;
-use std::path::{Path, PathBuf};
+mod bimap;
+mod diff;
+mod error;
+mod export;
+mod pack;
+mod parser;
+mod query;
+mod reach;
+mod rules;
+mod validate;
+
+use error::*;
+use export::Scope;
+use rules::BagRules;
+use snafu::{OptionExt, ResultExt};
+use std::env;
 
 fn main() {
-    println!("Part1: {:?}", part1());
-    println!("Part2: {:?}", part2());
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.split_first() {
+        Some((command, rest)) if command == "diff" => run_diff(rest),
+        _ => BagRules::from_file("../i")
+            .and_then(|rules| {
+                rules.validate()?;
+                Ok(rules)
+            })
+            .and_then(|rules| run(&rules, &args)),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+    }
 }
 
-fn part1() -> Result<usize> {
-    let e = read_lines("../i")?
-        .flatten()
-        .map(edges)
-        .flatten()
-        .flatten()
-        .collect::<Vec<_>>();
-    let nodes = e
-        .iter()
-        .map(|(a, b)| vec![a, b])
-        .flatten()
-        .unique()
-        .collect::<Vec<_>>();
-    let mut g = DiGraph::<_, ()>::new();
-    let mut indexes = HashMap::new();
-    for node in nodes {
-        indexes.insert(node, g.add_node(node));
-    }
-    for (a, b) in &e {
-        g.add_edge(
-            *indexes.get(&a).context(InvalidLine {})?,
-            *indexes.get(&b).context(InvalidLine {})?,
-            (),
-        );
+// diff <old file> <new file> [colour]
+fn run_diff(args: &[String]) -> Result<()> {
+    match args {
+        [old, new, colour @ ..] => {
+            let old = BagRules::from_file(old)?;
+            let new = BagRules::from_file(new)?;
+            // repeated edges would be merged and hide changes
+            old.validate()?;
+            new.validate()?;
+            print!("{}", diff::diff(&old, &new));
+            print!("{}", diff::diff_answers(&old, &new, &self::colour(colour)));
+            Ok(())
+        }
+        _ => BadOption {
+            option: args.join(" "),
+        }
+        .fail(),
     }
-    let start = indexes
-        .remove(&"shiny gold".to_string())
-        .context(InvalidLine {})?;
-    Ok(indexes
-        .iter()
-        .filter(|(_, i)| has_path_connecting(&g, **i, start, None))
-        .count())
 }
 
-fn part2() -> Result<usize> {
-    let e = read_lines("../i")?
-        .flatten()
-        .map(edges)
-        .flatten()
-        .flatten()
-        .collect::<Vec<_>>();
-    let nodes = e
-        .iter()
-        .map(|(a, b)| vec![a, b])
-        .flatten()
-        .unique()
-        .collect::<Vec<_>>();
-    let mut g = DiGraph::<_, ()>::new();
-    let mut indexes_bimap = BiMap::new();
-    for node in nodes {
-        let index = g.add_node(node);
-        indexes_bimap.insert(node, index);
-    }
-    for (a, b) in &e {
-        g.add_edge(
-            *indexes_bimap.get_forward(&a).context(InvalidLine {})?,
-            *indexes_bimap.get_forward(&b).context(InvalidLine {})?,
-            (),
-        );
+fn run(rules: &BagRules, args: &[String]) -> Result<()> {
+    match args.split_first() {
+        None => {
+            println!("Part1: {:?}", part1(rules));
+            println!("Part2: {:?}", part2(rules));
+        }
+        Some((command, rest)) => match command.as_str() {
+            "dot" => {
+                let options = Options::parse(rest)?;
+                let highlight = options
+                    .highlight
+                    .as_ref()
+                    .or(options.from.as_ref())
+                    .or(options.to.as_ref())
+                    .map_or("shiny gold", String::as_str);
+                print!(
+                    "{}",
+                    rules.to_dot(&rules.subgraph(&options.scope())?, Some(highlight))
+                );
+            }
+            "json" => println!(
+                "{}",
+                rules.to_json(&rules.subgraph(&Options::parse(rest)?.scope())?)?
+            ),
+            "ancestors" => {
+                let mut ancestors = rules.containers(&colour(rest))?;
+                ancestors.sort();
+                for ancestor in ancestors {
+                    println!("{}", ancestor);
+                }
+            }
+            "descendants" => {
+                for (descendant, count) in rules.descendants(&colour(rest))? {
+                    println!("{} {}", count, descendant);
+                }
+            }
+            "tree" => print!("{}", rules.tree(&colour(rest), None)?),
+            "chain" => println!("{}", rules.longest_chain(&colour(rest))?.join(" -> ")),
+            "leaves" => {
+                let colour = colour(rest);
+                let scope = if rest.is_empty() {
+                    None
+                } else {
+                    Some(colour.as_str())
+                };
+                for leaf in rules.leaves(scope)? {
+                    println!("{}", leaf);
+                }
+            }
+            "can-contain" => {
+                let index = rules.ancestor_index()?;
+                for (outer, inner) in containment_pairs(rest)? {
+                    println!(
+                        "{} -> {}: {}",
+                        outer,
+                        inner,
+                        rules.can_contain(&index, &outer, &inner)?
+                    );
+                }
+            }
+            "pack" => {
+                let tree = rest.iter().any(|arg| arg == "--tree");
+                let budget = rest
+                    .iter()
+                    .find(|arg| *arg != "--tree")
+                    .context(BadOption {
+                        option: "pack needs a budget",
+                    })?
+                    .parse()
+                    .context(ParseInt {})?;
+                for (colour, bags) in rules.packable(budget)? {
+                    println!("{} bags in total using {}", bags, colour);
+                    if tree {
+                        print!("{}", rules.tree(colour, None)?);
+                    }
+                }
+            }
+            "cheapest" => {
+                let colour = colour(rest);
+                match rules.cheapest_holder(&colour)? {
+                    Some((outer, bags)) => {
+                        println!("{} bags in total using {}", bags, outer);
+                        print!("{}", rules.tree(outer, Some(&colour))?);
+                    }
+                    None => println!("No bag can hold {}", colour),
+                }
+            }
+            _ => return UnknownCommand { command }.fail(),
+        },
     }
-    let w: HashMap<_, _> = read_lines("../i")?
-        .flatten()
-        .map(weights)
-        .flatten()
-        .flatten()
-        .collect();
-    Ok(bags(
-        "shiny gold".to_string(),
-        &g,
-        &w,
-        &|s| indexes_bimap.get_forward(s).context(InvalidLine {}),
-        &|s| Ok(indexes_bimap.get_reverse(&s).context(InvalidLine {})?),
-    )?)
+    Ok(())
 }
 
-fn bags<'a, I: Fn(&String) -> Result<&'a NodeIndex>, R: Fn(NodeIndex) -> Result<&'a String>>(
-    s: String,
-    g: &DiGraph<&std::string::String, ()>,
-    w: &HashMap<(String, String), usize>,
-    get_i: &I,
-    get_r: &R,
-) -> Result<usize> {
-    g.neighbors(*get_i(&s)?)
-        .map(|x| {
-            let right = get_r(x)?;
-            Ok(w.get(&(s.clone(), right.to_string()))
-                .context(InvalidLine {})?
-                * (1 + bags(right.to_string(), g, w, get_i, get_r)?))
-        })
-        .sum::<Result<_>>()
+fn part1(rules: &BagRules) -> Result<usize> {
+    Ok(rules.containers("shiny gold")?.len())
 }
 
-fn edges(line: String) -> Result<Vec<(String, String)>> {
-    let v = line
-        .split(" contain ")
-        .map(|s| s.to_string())
-        .collect::<Vec<_>>();
-    let left = v.first().context(InvalidLine {})?;
-    let right = v.last().context(InvalidLine {})?;
-    if right.starts_with("no") {
-        Ok(vec![])
-    } else {
-        let a = upto_last_space(left)?;
-        right[..(right.len() - 1)]
-            .split(", ")
-            .map(|s| s.to_string())
-            .map(|s: String| Ok((a.clone(), upto_last_space(&s)?[2..].to_string())))
-            .collect::<Result<Vec<_>>>()
-    }
+fn part2(rules: &BagRules) -> Result<usize> {
+    rules.contained_count("shiny gold")
 }
 
-fn weights(line: String) -> Result<HashMap<(String, String), usize>> {
-    let v = line
-        .split(" contain ")
-        .map(|s| s.to_string())
-        .collect::<Vec<_>>();
-    let left = v.first().context(InvalidLine {})?;
-    let right = v.last().context(InvalidLine {})?;
-    if right.starts_with("no") {
-        Ok(HashMap::new())
+// Colour names may be passed quoted or as separate words
+fn colour(args: &[String]) -> String {
+    if args.is_empty() {
+        "shiny gold".to_string()
     } else {
-        let a = upto_last_space(left)?;
-        right[..(right.len() - 1)]
-            .split(", ")
-            .map(|s| s.to_string())
-            .map(|s: String| {
-                Ok((
-                    (a.clone(), upto_last_space(&s)?[2..].to_string()),
-                    upto_last_space(&s)?[..1].parse().context(ParseInt {})?,
-                ))
-            })
-            .collect::<Result<HashMap<_, _>>>()
+        args.join(" ")
     }
 }
 
-fn upto_last_space(s: &String) -> Result<String> {
-    let last_space = s.rfind(" ").context(InvalidLine {})?;
-    Ok(s[..last_space].to_string())
-}
-
-#[derive(Debug, Snafu)]
-enum Error {
-    #[snafu(display("Could not open file {}: {}", filename.display(), source))]
-    OpenFile {
-        filename: PathBuf,
-        source: std::io::Error,
-    },
-    #[snafu(display("Could not find any valid lines"))]
-    InvalidLine {},
-    #[snafu(display("Could not open parse int: {}", source))]
-    ParseInt { source: ParseIntError },
-}
-
-type Result<T, E = Error> = std::result::Result<T, E>;
-
-// The output is wrapped in a Result to allow matching on errors
-// Returns an Iterator to the Reader of the lines of the file.
-fn read_lines<P>(filename: P) -> Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(&filename).context(OpenFile {
-        filename: filename.as_ref(),
-    })?;
-    Ok(io::BufReader::new(file).lines())
+// --outer <colour> --inner <colour> [--inner <colour>...] [--outer ...]
+// Each inner colour is checked against the outer colour before it
+fn containment_pairs(args: &[String]) -> Result<Vec<(String, String)>> {
+    let mut pairs = vec![];
+    let mut outer = None;
+    let mut rest = args;
+    while let Some((flag, tail)) = rest.split_first() {
+        let end = tail
+            .iter()
+            .position(|arg| arg.starts_with("--"))
+            .unwrap_or(tail.len());
+        let (words, tail) = tail.split_at(end);
+        match (flag.as_str(), &outer) {
+            _ if words.is_empty() => return BadOption { option: flag }.fail(),
+            ("--outer", _) => outer = Some(colour(words)),
+            ("--inner", Some(outer)) => pairs.push((outer.clone(), colour(words))),
+            _ => return BadOption { option: flag }.fail(),
+        }
+        rest = tail;
+    }
+    if pairs.is_empty() {
+        return BadOption {
+            option: "can-contain needs --outer and --inner colours",
+        }
+        .fail();
+    }
+    Ok(pairs)
 }
 
-struct BiMap<S, T> {
-    forward: HashMap<S, T>,
-    reverse: HashMap<T, S>,
+#[derive(Default)]
+struct Options {
+    from: Option<String>,
+    to: Option<String>,
+    highlight: Option<String>,
 }
 
-impl<S, T> BiMap<S, T>
-where
-    S: std::cmp::Eq,
-    S: std::hash::Hash,
-    T: std::cmp::Eq,
-    T: std::hash::Hash,
-    S: Copy,
-    T: Copy,
-{
-    fn new<'b>() -> BiMap<S, T> {
-        BiMap {
-            forward: HashMap::new(),
-            reverse: HashMap::new(),
+impl Options {
+    fn parse(args: &[String]) -> Result<Options> {
+        let mut options = Options::default();
+        for pair in args.chunks(2) {
+            match pair {
+                [flag, value] if flag == "--from" => options.from = Some(value.clone()),
+                [flag, value] if flag == "--to" => options.to = Some(value.clone()),
+                [flag, value] if flag == "--highlight" => options.highlight = Some(value.clone()),
+                _ => {
+                    return BadOption {
+                        option: pair.join(" "),
+                    }
+                    .fail()
+                }
+            }
         }
+        Ok(options)
     }
 
-    fn insert(&mut self, s: S, t: T) {
-        self.forward.insert(s, t);
-        self.reverse.insert(t, s);
-    }
-    #[inline]
-    fn get_forward<K>(&self, s: &K) -> Option<&T>
-    where
-        S: Borrow<K>,
-        K: std::cmp::Eq + std::hash::Hash,
-    {
-        Some(self.forward.get(&s)?)
-    }
-    #[inline]
-    fn get_reverse<K>(&self, t: &K) -> Option<&S>
-    where
-        T: Borrow<K>,
-        K: std::cmp::Eq + std::hash::Hash,
-    {
-        Some(self.reverse.get(&t)?)
+    fn scope(&self) -> Scope {
+        match (&self.from, &self.to) {
+            (Some(colour), _) => Scope::From(colour.clone()),
+            (None, Some(colour)) => Scope::To(colour.clone()),
+            (None, None) => Scope::All,
+        }
     }
 }
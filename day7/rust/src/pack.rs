@@ -0,0 +1,88 @@
+use error::*;
+use petgraph::Direction;
+use rules::BagRules;
+
+impl BagRules {
+    // Outermost colours, held by no other rule, that can be packed within
+    // the budget with the outer bag included, cheapest first
+    pub fn packable(&self, budget: usize) -> Result<Vec<(&str, usize)>> {
+        let costs = self.packing_costs()?;
+        let graph = self.graph();
+        let mut packable = graph
+            .node_indices()
+            .filter(|&index| {
+                graph
+                    .neighbors_directed(index, Direction::Incoming)
+                    .next()
+                    .is_none()
+            })
+            .filter_map(|index| {
+                let bags = costs[index.index()].filter(|&bags| bags <= budget)?;
+                Some(self.colour(index).map(|colour| (colour, bags)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        packable.sort_by_key(|&(colour, bags)| (bags, colour));
+        Ok(packable)
+    }
+
+    // The outer bag that needs the fewest bags in total to end up holding
+    // the given colour
+    pub fn cheapest_holder(&self, colour: &str) -> Result<Option<(&str, usize)>> {
+        let costs = self.packing_costs()?;
+        let mut holders = vec![];
+        for outer in self.containers(colour)? {
+            if let Some(bags) = costs[self.index(outer)?.index()] {
+                holders.push((outer, bags));
+            }
+        }
+        Ok(holders
+            .into_iter()
+            .min_by_key(|&(colour, bags)| (bags, colour)))
+    }
+
+    // Bags needed to fully pack one bag of each colour, indexed by node, with
+    // None where the total overflows
+    fn packing_costs(&self) -> Result<Vec<Option<usize>>> {
+        Ok(self
+            .totals()?
+            .into_iter()
+            .map(|total| total?.checked_add(1))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rules::{BagRules, EXAMPLE};
+
+    fn example() -> BagRules {
+        BagRules::parse(EXAMPLE.iter().map(|line| line.to_string())).unwrap()
+    }
+
+    #[test]
+    fn packs_only_outermost_colours() {
+        let rules = example();
+        // light red needs 1 + 34 + 2 * 76 bags, dark orange 1 + 3 * 34 + 4 * 76
+        assert_eq!(rules.packable(100).unwrap(), vec![]);
+        assert_eq!(rules.packable(187).unwrap(), vec![("light red", 187)]);
+        assert_eq!(
+            rules.packable(1000).unwrap(),
+            vec![("light red", 187), ("dark orange", 407)]
+        );
+    }
+
+    #[test]
+    fn cheapest_holder() {
+        let rules = example();
+        assert_eq!(
+            rules.cheapest_holder("shiny gold").unwrap(),
+            Some(("bright white", 34))
+        );
+        assert_eq!(
+            rules.cheapest_holder("faded blue").unwrap(),
+            Some(("dark olive", 8))
+        );
+        assert_eq!(rules.cheapest_holder("light red").unwrap(), None);
+        assert!(rules.cheapest_holder("no such").is_err());
+    }
+}
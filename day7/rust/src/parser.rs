@@ -0,0 +1,191 @@
+use error::*;
+
+// rule     := colour " bags contain " contents ["."]
+// contents := "no other bags" | item (", " item)*
+// item     := count " " colour " " ("bag" | "bags")
+// colour   := word (" " word)*
+// word     := (letter | digit)+
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    pub outer: String,
+    pub contents: Vec<(usize, String)>,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+pub fn parse_rule(line: usize, input: &str) -> Result<Rule> {
+    let mut parser = Parser {
+        input: input.trim_end(),
+        pos: 0,
+        line,
+    };
+    let outer = parser.colour()?;
+    parser.literal(" contain ")?;
+    let contents = if parser.rest().starts_with("no other bags") {
+        parser.literal("no other bags")?;
+        vec![]
+    } else {
+        let mut contents = vec![parser.item()?];
+        while parser.rest().starts_with(", ") {
+            parser.literal(", ")?;
+            contents.push(parser.item()?);
+        }
+        contents
+    };
+    if parser.rest().starts_with('.') {
+        parser.pos += 1;
+    }
+    if !parser.rest().is_empty() {
+        return parser.fail("\", \", \".\" or end of line");
+    }
+    Ok(Rule { outer, contents })
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn fail<T>(&self, expected: &str) -> Result<T> {
+        Syntax {
+            line: self.line,
+            column: self.input[..self.pos].chars().count() + 1,
+            expected,
+        }
+        .fail()
+    }
+
+    fn literal(&mut self, literal: &str) -> Result<()> {
+        if self.rest().starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            self.fail(&format!("{:?}", literal))
+        }
+    }
+
+    fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(rest.len());
+        self.pos += end;
+        &rest[..end]
+    }
+
+    // Words up to and including the closing "bag" or "bags"
+    fn colour(&mut self) -> Result<String> {
+        let mut words = vec![];
+        loop {
+            let start = self.pos;
+            match self.word() {
+                "" => {
+                    self.pos = start;
+                    return self.fail("a colour word");
+                }
+                "bag" | "bags" if words.is_empty() => {
+                    self.pos = start;
+                    return self.fail("a colour before \"bags\"");
+                }
+                "bag" | "bags" => return Ok(words.join(" ")),
+                word => words.push(word),
+            }
+            if self.literal(" ").is_err() {
+                return self.fail("\" bag\" or \" bags\"");
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<(usize, String)> {
+        let digits = self.rest().len()
+            - self
+                .rest()
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        if digits == 0 {
+            return self.fail("a bag count");
+        }
+        let count = match self.rest()[..digits].parse() {
+            Ok(count) => count,
+            Err(_) => return self.fail("a count that fits in a usize"),
+        };
+        self.pos += digits;
+        self.literal(" ")?;
+        Ok((count, self.colour()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_rule, Rule};
+
+    fn rule(outer: &str, contents: &[(usize, &str)]) -> Rule {
+        Rule {
+            outer: outer.to_string(),
+            contents: contents
+                .iter()
+                .map(|&(count, colour)| (count, colour.to_string()))
+                .collect(),
+        }
+    }
+
+    fn error(input: &str) -> String {
+        parse_rule(1, input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn multi_digit_counts() {
+        assert_eq!(
+            parse_rule(
+                1,
+                "light red bags contain 12 bright white bags, 345 muted yellow bags."
+            )
+            .unwrap(),
+            rule("light red", &[(12, "bright white"), (345, "muted yellow")])
+        );
+    }
+
+    #[test]
+    fn colours_of_any_length() {
+        assert_eq!(
+            parse_rule(1, "red bags contain 1 very pale shiny gold bag.").unwrap(),
+            rule("red", &[(1, "very pale shiny gold")])
+        );
+    }
+
+    #[test]
+    fn singular_plural_and_optional_period() {
+        assert_eq!(
+            parse_rule(1, "a b bags contain 1 c d bag, 2 e f bags").unwrap(),
+            rule("a b", &[(1, "c d"), (2, "e f")])
+        );
+        assert_eq!(
+            parse_rule(1, "a b bag contain no other bags.").unwrap(),
+            rule("a b", &[])
+        );
+    }
+
+    #[test]
+    fn reports_the_column() {
+        assert_eq!(
+            error("a b bags contain 1c d bag."),
+            "Syntax error on line 1 column 19: expected \" \""
+        );
+        assert_eq!(
+            error("a b bags contain c d bag."),
+            "Syntax error on line 1 column 18: expected a bag count"
+        );
+        assert_eq!(
+            error("bags contain no other bags."),
+            "Syntax error on line 1 column 1: expected a colour before \"bags\""
+        );
+        assert_eq!(
+            error("a b bags contain 1 c d bag; 2 e f bags."),
+            "Syntax error on line 1 column 27: expected \", \", \".\" or end of line"
+        );
+    }
+}
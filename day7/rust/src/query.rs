@@ -0,0 +1,180 @@
+use error::*;
+use export::Scope;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use rules::BagRules;
+use snafu::OptionExt;
+
+impl BagRules {
+    // Every colour inside one bag of the given colour, with how many of them
+    // it holds in total across all levels of nesting
+    pub fn descendants(&self, colour: &str) -> Result<Vec<(&str, usize)>> {
+        let graph = self.graph();
+        let start = self.index(colour)?;
+        let mut counts = vec![0usize; graph.node_count()];
+        counts[start.index()] = 1;
+        for index in self.order()? {
+            let count = counts[index.index()];
+            if count == 0 {
+                continue;
+            }
+            for edge in graph.edges(index) {
+                let inner = graph[edge.target()].as_str();
+                counts[edge.target().index()] = count
+                    .checked_mul(*edge.weight())
+                    .and_then(|n| n.checked_add(counts[edge.target().index()]))
+                    .context(Overflow { colour: inner })?;
+            }
+        }
+        let mut descendants = graph
+            .node_indices()
+            .filter(|&index| index != start && counts[index.index()] > 0)
+            .map(|index| Ok((self.colour(index)?, counts[index.index()])))
+            .collect::<Result<Vec<_>>>()?;
+        descendants.sort();
+        Ok(descendants)
+    }
+
+    // The containment tree of a colour with one indented line per bag,
+    // optionally marking every occurrence of another colour
+    pub fn tree(&self, colour: &str, mark: Option<&str>) -> Result<String> {
+        let mut out = format!("{}\n", colour);
+        self.write_tree(self.index(colour)?, 1, mark, &mut out)?;
+        Ok(out)
+    }
+
+    fn write_tree(
+        &self,
+        index: NodeIndex,
+        depth: usize,
+        mark: Option<&str>,
+        out: &mut String,
+    ) -> Result<()> {
+        let mut edges = self.graph().edges(index).collect::<Vec<_>>();
+        edges.reverse();
+        for edge in edges {
+            let inner = self.colour(edge.target())?;
+            *out += &format!(
+                "{}{} {}{}\n",
+                "  ".repeat(depth),
+                edge.weight(),
+                inner,
+                if Some(inner) == mark { " <--" } else { "" }
+            );
+            self.write_tree(edge.target(), depth + 1, mark, out)?;
+        }
+        Ok(())
+    }
+
+    // The deepest nesting of bags starting from the given colour
+    pub fn longest_chain(&self, colour: &str) -> Result<Vec<&str>> {
+        let graph = self.graph();
+        let mut next: Vec<Option<NodeIndex>> = vec![None; graph.node_count()];
+        let mut depth = vec![0; graph.node_count()];
+        for index in self.order()?.into_iter().rev() {
+            if let Some(inner) = graph
+                .neighbors(index)
+                .max_by_key(|inner| depth[inner.index()])
+            {
+                next[index.index()] = Some(inner);
+                depth[index.index()] = depth[inner.index()] + 1;
+            }
+        }
+        let mut chain = vec![];
+        let mut current = Some(self.index(colour)?);
+        while let Some(index) = current {
+            chain.push(self.colour(index)?);
+            current = next[index.index()];
+        }
+        Ok(chain)
+    }
+
+    // Colours that hold no other bags, limited to those inside the given colour
+    pub fn leaves(&self, colour: Option<&str>) -> Result<Vec<&str>> {
+        let scope = colour.map_or(Scope::All, |colour| Scope::From(colour.to_string()));
+        let mut leaves = self
+            .subgraph(&scope)?
+            .into_iter()
+            .filter(|&index| {
+                Some(self.graph()[index].as_str()) != colour
+                    && self
+                        .graph()
+                        .neighbors_directed(index, Direction::Outgoing)
+                        .next()
+                        .is_none()
+            })
+            .map(|index| self.colour(index))
+            .collect::<Result<Vec<_>>>()?;
+        leaves.sort();
+        Ok(leaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rules::{BagRules, EXAMPLE};
+
+    fn example() -> BagRules {
+        BagRules::parse(EXAMPLE.iter().map(|line| line.to_string())).unwrap()
+    }
+
+    #[test]
+    fn descendants_of_shiny_gold() {
+        let rules = example();
+        let descendants = rules.descendants("shiny gold").unwrap();
+        assert_eq!(
+            descendants,
+            vec![
+                ("dark olive", 1),
+                ("dotted black", 16),
+                ("faded blue", 13),
+                ("vibrant plum", 2),
+            ]
+        );
+        // one of each descendant per bag adds up to the part 2 answer
+        let total = descendants.iter().map(|&(_, count)| count).sum::<usize>();
+        assert_eq!(total, rules.contained_count("shiny gold").unwrap());
+        assert!(rules.descendants("faded blue").unwrap().is_empty());
+    }
+
+    #[test]
+    fn tree_marks_a_colour() {
+        assert_eq!(
+            example().tree("shiny gold", Some("faded blue")).unwrap(),
+            "shiny gold\n\
+             \x20 1 dark olive\n\
+             \x20   3 faded blue <--\n\
+             \x20   4 dotted black\n\
+             \x20 2 vibrant plum\n\
+             \x20   5 faded blue <--\n\
+             \x20   6 dotted black\n"
+        );
+    }
+
+    #[test]
+    fn longest_chain() {
+        let rules = example();
+        let chain = rules.longest_chain("light red").unwrap();
+        assert_eq!(chain.len(), 5);
+        assert_eq!(chain[..3], ["light red", "bright white", "shiny gold"]);
+        assert_eq!(
+            rules.longest_chain("faded blue").unwrap(),
+            vec!["faded blue"]
+        );
+    }
+
+    #[test]
+    fn leaves() {
+        let rules = example();
+        assert_eq!(
+            rules.leaves(None).unwrap(),
+            vec!["dotted black", "faded blue"]
+        );
+        assert_eq!(
+            rules.leaves(Some("dark olive")).unwrap(),
+            vec!["dotted black", "faded blue"]
+        );
+        assert!(rules.leaves(Some("faded blue")).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use error::*;
+use petgraph::graph::NodeIndex;
+use rules::BagRules;
+
+// For every colour, a bitset of the colours it can eventually contain
+pub struct AncestorIndex {
+    words: usize,
+    reach: Vec<u64>,
+}
+
+impl AncestorIndex {
+    pub fn can_contain(&self, outer: NodeIndex, inner: NodeIndex) -> bool {
+        let word = self.reach[outer.index() * self.words + inner.index() / 64];
+        word & (1 << (inner.index() % 64)) != 0
+    }
+}
+
+impl BagRules {
+    // Built bottom up so each colour merges the finished sets of its contents
+    pub fn ancestor_index(&self) -> Result<AncestorIndex> {
+        let graph = self.graph();
+        let words = graph.node_count().div_ceil(64);
+        let mut reach = vec![0u64; graph.node_count() * words];
+        for outer in self.order()?.into_iter().rev() {
+            for inner in graph.neighbors(outer) {
+                reach[outer.index() * words + inner.index() / 64] |= 1 << (inner.index() % 64);
+                for word in 0..words {
+                    reach[outer.index() * words + word] |= reach[inner.index() * words + word];
+                }
+            }
+        }
+        Ok(AncestorIndex { words, reach })
+    }
+
+    pub fn can_contain(&self, index: &AncestorIndex, outer: &str, inner: &str) -> Result<bool> {
+        Ok(index.can_contain(self.index(outer)?, self.index(inner)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rules::{BagRules, EXAMPLE};
+
+    // The index must agree with a reverse search for every pair of colours
+    fn check_all_pairs(rules: &BagRules) {
+        let index = rules.ancestor_index().unwrap();
+        let graph = rules.graph();
+        for inner in graph.node_indices() {
+            let containers = rules.containers(&graph[inner]).unwrap();
+            for outer in graph.node_indices() {
+                assert_eq!(
+                    index.can_contain(outer, inner),
+                    containers.contains(&graph[outer].as_str()),
+                    "{} -> {}",
+                    graph[outer],
+                    graph[inner]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn example_pairs() {
+        check_all_pairs(&BagRules::parse(EXAMPLE.iter().map(|line| line.to_string())).unwrap());
+    }
+
+    #[test]
+    fn pairs_across_several_words() {
+        // more than 64 colours so reach sets span several words
+        let lines = (0..150).map(|i| match (i + 3 < 150, i + 64 < 150) {
+            (true, true) => format!("c{} bags contain 1 c{} bag, 2 c{} bags.", i, i + 3, i + 64),
+            (true, false) => format!("c{} bags contain 1 c{} bag.", i, i + 3),
+            _ => format!("c{} bags contain no other bags.", i),
+        });
+        check_all_pairs(&BagRules::parse(lines).unwrap());
+    }
+}
@@ -0,0 +1,215 @@
+use bimap::BiMap;
+use error::*;
+use parser::{parse_rule, Rule};
+use petgraph::algo::toposort;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::{Bfs, EdgeRef, Reversed};
+use snafu::{OptionExt, ResultExt};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+// Bag rules as a graph with an edge from each bag to the bags it directly
+// contains, weighted by how many of them it holds.
+pub struct BagRules {
+    graph: DiGraph<String, usize>,
+    colours: BiMap<String, NodeIndex>,
+    // colours with a rule of their own, and those given more than one
+    defined: HashSet<NodeIndex>,
+    duplicates: Vec<NodeIndex>,
+}
+
+impl BagRules {
+    pub fn from_file<P>(filename: P) -> Result<BagRules>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(&filename).context(OpenFile {
+            filename: filename.as_ref(),
+        })?;
+        BagRules::parse(
+            io::BufReader::new(file)
+                .lines()
+                .collect::<io::Result<Vec<_>>>()
+                .context(ReadLine {})?,
+        )
+    }
+
+    pub fn parse<I>(lines: I) -> Result<BagRules>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut rules = BagRules {
+            graph: DiGraph::new(),
+            colours: BiMap::new(),
+            defined: HashSet::new(),
+            duplicates: vec![],
+        };
+        for (i, line) in lines.into_iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Rule { outer, contents } = parse_rule(i + 1, &line)?;
+            let outer = rules.add_colour(outer);
+            if !rules.defined.insert(outer) {
+                rules.duplicates.push(outer);
+            }
+            for (count, inner) in contents {
+                let inner = rules.add_colour(inner);
+                rules.graph.add_edge(outer, inner, count);
+            }
+        }
+        Ok(rules)
+    }
+
+    fn add_colour(&mut self, colour: String) -> NodeIndex {
+        if let Some(index) = self.colours.get_by_left(&colour) {
+            return *index;
+        }
+        let index = self.graph.add_node(colour.clone());
+        self.colours.insert(colour, index);
+        index
+    }
+
+    pub fn graph(&self) -> &DiGraph<String, usize> {
+        &self.graph
+    }
+
+    pub fn is_defined(&self, index: NodeIndex) -> bool {
+        self.defined.contains(&index)
+    }
+
+    pub fn duplicates(&self) -> &[NodeIndex] {
+        &self.duplicates
+    }
+
+    pub fn index(&self, colour: &str) -> Result<NodeIndex> {
+        self.colours
+            .get_by_left(colour)
+            .cloned()
+            .context(UnknownColour { colour })
+    }
+
+    pub fn colour(&self, index: NodeIndex) -> Result<&str> {
+        self.colours
+            .get_by_right(&index)
+            .map(String::as_str)
+            .context(UnknownNode { index })
+    }
+
+    // Every colour that can eventually contain the given colour, found with
+    // a single search up the reversed edges
+    pub fn containers(&self, colour: &str) -> Result<Vec<&str>> {
+        let target = self.index(colour)?;
+        let reversed = Reversed(&self.graph);
+        let mut bfs = Bfs::new(reversed, target);
+        let mut containers = vec![];
+        while let Some(index) = bfs.next(reversed) {
+            if index != target {
+                containers.push(self.colour(index)?);
+            }
+        }
+        Ok(containers)
+    }
+
+    // Total number of bags inside one bag of the given colour
+    pub fn contained_count(&self, colour: &str) -> Result<usize> {
+        let index = self.index(colour)?;
+        self.totals()?[index.index()].context(Overflow { colour })
+    }
+
+    // Bags inside one bag of every colour, indexed by node. Each colour is
+    // evaluated once after everything it contains; None marks a total that
+    // does not fit in a usize.
+    pub fn totals(&self) -> Result<Vec<Option<usize>>> {
+        let order = self.order()?;
+        let mut totals: Vec<Option<usize>> = vec![Some(0); self.graph.node_count()];
+        for &index in order.iter().rev() {
+            totals[index.index()] = self.graph.edges(index).try_fold(0usize, |sum, edge| {
+                totals[edge.target().index()]?
+                    .checked_add(1)?
+                    .checked_mul(*edge.weight())?
+                    .checked_add(sum)
+            });
+        }
+        Ok(totals)
+    }
+
+    // Colours ordered so that every bag comes before the bags it contains
+    pub fn order(&self) -> Result<Vec<NodeIndex>> {
+        toposort(&self.graph, None).map_err(|cycle| Error::Cycle {
+            colour: self.graph[cycle.node_id()].clone(),
+        })
+    }
+}
+
+// The example rules from the puzzle
+#[cfg(test)]
+pub const EXAMPLE: [&str; 9] = [
+    "light red bags contain 1 bright white bag, 2 muted yellow bags.",
+    "dark orange bags contain 3 bright white bags, 4 muted yellow bags.",
+    "bright white bags contain 1 shiny gold bag.",
+    "muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.",
+    "shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.",
+    "dark olive bags contain 3 faded blue bags, 4 dotted black bags.",
+    "vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.",
+    "faded blue bags contain no other bags.",
+    "dotted black bags contain no other bags.",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{BagRules, EXAMPLE};
+
+    fn rules(lines: &[&str]) -> BagRules {
+        BagRules::parse(lines.iter().map(|line| line.to_string())).unwrap()
+    }
+
+    #[test]
+    fn example_answers() {
+        let rules = rules(&EXAMPLE);
+        assert_eq!(rules.containers("shiny gold").unwrap().len(), 4);
+        assert_eq!(rules.contained_count("shiny gold").unwrap(), 32);
+    }
+
+    #[test]
+    fn counts_nested_bags() {
+        let rules = rules(&[
+            "shiny gold bags contain 2 dark red bags.",
+            "dark red bags contain 2 dark orange bags.",
+            "dark orange bags contain 2 dark yellow bags.",
+            "dark yellow bags contain 2 dark green bags.",
+            "dark green bags contain 2 dark blue bags.",
+            "dark blue bags contain 2 dark violet bags.",
+            "dark violet bags contain no other bags.",
+        ]);
+        assert_eq!(rules.contained_count("shiny gold").unwrap(), 126);
+    }
+
+    #[test]
+    fn shared_bags_are_counted_once_per_path() {
+        // a diamond: top holds left and right, which both hold bottom
+        let rules = rules(&[
+            "top bags contain 2 left bags, 3 right bags.",
+            "left bags contain 4 bottom bags.",
+            "right bags contain 5 bottom bags.",
+            "bottom bags contain no other bags.",
+        ]);
+        assert_eq!(rules.contained_count("top").unwrap(), 2 + 8 + 3 + 15);
+    }
+
+    #[test]
+    fn reports_overflow_instead_of_wrapping() {
+        let lines = (0..20)
+            .map(|i| format!("c{} bags contain 1000 c{} bags.", i, i + 1))
+            .chain(vec![String::from("c20 bags contain no other bags.")])
+            .collect::<Vec<_>>();
+        let rules = BagRules::parse(lines).unwrap();
+        assert_eq!(rules.contained_count("c18").unwrap(), 1_001_000);
+        assert_eq!(
+            rules.contained_count("c0").unwrap_err().to_string(),
+            "Number of bags inside c0 overflows"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use error::*;
+use petgraph::graph::NodeIndex;
+use rules::BagRules;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    // colours along the cycle, starting and ending with the same colour
+    Cycle(Vec<String>),
+    SelfReference(String),
+    Undefined(String),
+    Duplicate(String),
+    // a rule listing the same inner colour more than once
+    Repeated(String, String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Cycle(path) => write!(f, "cycle {}", path.join(" -> ")),
+            Problem::SelfReference(colour) => write!(f, "{} contains itself", colour),
+            Problem::Undefined(colour) => write!(f, "{} has no rule", colour),
+            Problem::Duplicate(colour) => write!(f, "{} has more than one rule", colour),
+            Problem::Repeated(outer, inner) => {
+                write!(f, "{} lists {} more than once", outer, inner)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    New,
+    Active,
+    Done,
+}
+
+impl BagRules {
+    pub fn validate(&self) -> Result<()> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidRules { problems })
+        }
+    }
+
+    pub fn problems(&self) -> Vec<Problem> {
+        let graph = self.graph();
+        let name = |index: NodeIndex| graph[index].clone();
+        let mut problems = self
+            .duplicates()
+            .iter()
+            .map(|&index| Problem::Duplicate(name(index)))
+            .collect::<Vec<_>>();
+        for index in graph.node_indices() {
+            if !self.is_defined(index) {
+                problems.push(Problem::Undefined(name(index)));
+            }
+            if graph.contains_edge(index, index) {
+                problems.push(Problem::SelfReference(name(index)));
+            }
+            // a duplicate rule already explains any repeats
+            if !self.duplicates().contains(&index) {
+                let mut inner = graph.neighbors(index).collect::<Vec<_>>();
+                inner.sort();
+                for pair in inner.windows(2).filter(|pair| pair[0] == pair[1]) {
+                    problems.push(Problem::Repeated(name(index), name(pair[0])));
+                }
+            }
+        }
+        problems.extend(
+            self.cycles()
+                .into_iter()
+                .map(|cycle| Problem::Cycle(cycle.into_iter().map(name).collect())),
+        );
+        problems
+    }
+
+    // Depth first search reporting the path closed by every back edge.
+    // Self references are left to problems().
+    fn cycles(&self) -> Vec<Vec<NodeIndex>> {
+        let graph = self.graph();
+        let mut marks = vec![Mark::New; graph.node_count()];
+        let mut cycles = vec![];
+        for start in graph.node_indices() {
+            if marks[start.index()] != Mark::New {
+                continue;
+            }
+            marks[start.index()] = Mark::Active;
+            let mut path = vec![start];
+            let mut stack = vec![graph.neighbors(start)];
+            while let Some(neighbors) = stack.last_mut() {
+                let current = path[path.len() - 1];
+                match neighbors.next() {
+                    Some(next) if next == current => {}
+                    Some(next) => match marks[next.index()] {
+                        Mark::New => {
+                            marks[next.index()] = Mark::Active;
+                            path.push(next);
+                            stack.push(graph.neighbors(next));
+                        }
+                        Mark::Active => {
+                            let from = path.iter().position(|&i| i == next).unwrap_or(0);
+                            let mut cycle = path[from..].to_vec();
+                            cycle.push(next);
+                            cycles.push(cycle);
+                        }
+                        Mark::Done => {}
+                    },
+                    None => {
+                        marks[current.index()] = Mark::Done;
+                        path.pop();
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Problem;
+    use rules::BagRules;
+
+    fn problems(lines: &[&str]) -> Vec<Problem> {
+        BagRules::parse(lines.iter().map(|line| line.to_string()))
+            .unwrap()
+            .problems()
+    }
+
+    fn owned(colours: &[&str]) -> Vec<String> {
+        colours.iter().map(|colour| colour.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_rules_have_no_problems() {
+        assert_eq!(
+            problems(&["a bags contain 1 b bag.", "b bags contain no other bags.",]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn reports_cycle_path() {
+        assert_eq!(
+            problems(&[
+                "a bags contain 1 b bag.",
+                "b bags contain 2 c bags.",
+                "c bags contain 3 a bags.",
+            ]),
+            vec![Problem::Cycle(owned(&["a", "b", "c", "a"]))]
+        );
+    }
+
+    #[test]
+    fn reports_self_reference_undefined_and_duplicate() {
+        assert_eq!(
+            problems(&[
+                "a bags contain 1 a bag, 2 missing bags.",
+                "a bags contain no other bags.",
+            ]),
+            vec![
+                Problem::Duplicate(String::from("a")),
+                Problem::SelfReference(String::from("a")),
+                Problem::Undefined(String::from("missing")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_repeated_inner_colours() {
+        assert_eq!(
+            problems(&[
+                "a bags contain 1 b bag, 2 b bags.",
+                "b bags contain no other bags.",
+            ]),
+            vec![Problem::Repeated(String::from("a"), String::from("b"))]
+        );
+    }
+
+    #[test]
+    fn validate_fails_with_every_problem() {
+        let rules = BagRules::parse(vec![String::from("a bags contain 1 b bag.")]).unwrap();
+        assert_eq!(
+            rules.validate().unwrap_err().to_string(),
+            "Invalid bag rules: b has no rule"
+        );
+    }
+}
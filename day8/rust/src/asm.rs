@@ -0,0 +1,166 @@
+use error::*;
+use instruction::Instruction;
+use opcodes::InstructionSet;
+use snafu::ResultExt;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+// Assembles source with labels, comments (# or ;) and blank lines. A label
+// is written `name:` before an instruction or on its own line, and a value
+// operand naming a label becomes the offset from the instruction to it.
+pub fn assemble(source: &str, set: &InstructionSet) -> Result<Vec<Instruction>> {
+    let mut labels = HashMap::new();
+    let mut lines = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let mut rest = line.split(['#', ';']).next().unwrap_or_default().trim();
+        while let Some((label, after)) = split_label(rest) {
+            if labels.insert(label, lines.len()).is_some() {
+                return Err(Box::new(Error::DuplicateLabel {
+                    label: label.to_string(),
+                }))
+                .context(Assembly { line: i + 1 });
+            }
+            rest = after;
+        }
+        if !rest.is_empty() {
+            lines.push((i + 1, rest));
+        }
+    }
+    lines
+        .iter()
+        .enumerate()
+        .map(|(address, &(line, text))| {
+            resolve(text, address, &labels, set)
+                .map_err(Box::new)
+                .context(Assembly { line })
+        })
+        .collect()
+}
+
+fn split_label(s: &str) -> Option<(&str, &str)> {
+    let (label, rest) = s.split_once(':')?;
+    if is_label(label) {
+        Some((label, rest.trim_start()))
+    } else {
+        None
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn resolve(
+    text: &str,
+    address: usize,
+    labels: &HashMap<&str, usize>,
+    set: &InstructionSet,
+) -> Result<Instruction> {
+    let mut words = text
+        .split_whitespace()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    // the value operand follows the register, if the opcode has one
+    let value = set
+        .get(&words[0])
+        .map(|opcode| opcode.signature())
+        .filter(|signature| signature.value)
+        .map(|signature| 1 + signature.register as usize);
+    if let Some(word) = value.and_then(|i| words.get_mut(i)) {
+        if is_label(word) {
+            let target = *labels.get(word.as_str()).ok_or(Error::UndefinedLabel {
+                label: word.clone(),
+            })?;
+            *word = format!("{:+}", target as i64 - address as i64);
+        }
+    }
+    set.parse(&words.join(" "))
+}
+
+// Turns a program back into assembler source. Every jump target gets a label
+// named after its address, listing the addresses that jump there, and jumps
+// that leave the program keep their numeric offsets.
+pub fn disassemble(program: &[Instruction]) -> String {
+    let mut sources = BTreeMap::new();
+    for (address, instruction) in program.iter().enumerate() {
+        if let Some(target) = jump_target(program, address, instruction) {
+            sources.entry(target).or_insert_with(Vec::new).push(address);
+        }
+    }
+    let mut out = String::new();
+    for address in 0..=program.len() {
+        if let Some(from) = sources.get(&address) {
+            let from = from.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+            writeln!(out, "{}:  # from {}", label(address), from.join(", ")).unwrap();
+        }
+        let instruction = match program.get(address) {
+            Some(instruction) => instruction,
+            None => break,
+        };
+        let mut text = instruction.to_string();
+        if let Some(target) = jump_target(program, address, instruction) {
+            let offset = format!("{:+}", instruction.val);
+            text.truncate(text.len() - offset.len());
+            text.push_str(&label(target));
+        }
+        writeln!(out, "    {:<16}# {}", text, address).unwrap();
+    }
+    out
+}
+
+fn jump_target(
+    program: &[Instruction],
+    address: usize,
+    instruction: &Instruction,
+) -> Option<usize> {
+    if !instruction.is_jump() {
+        return None;
+    }
+    let target = address as i64 + i64::from(instruction.val);
+    if target >= 0 && target <= program.len() as i64 {
+        Some(target as usize)
+    } else {
+        None
+    }
+}
+
+fn label(address: usize) -> String {
+    format!("l{}", address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_and_comments() {
+        let source = "\
+# count down from three
+start:  acc +3
+loop:
+    acc -1    ; one less
+    jmp done
+    nop loop
+done: jmp end
+end:
+";
+        let program = assemble(source, &InstructionSet::default()).unwrap();
+        let lines = program.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        assert_eq!(lines, ["acc +3", "acc -1", "jmp +2", "nop -2", "jmp +1"]);
+    }
+
+    #[test]
+    fn round_trips_through_disassembly() {
+        let source = ::std::fs::read_to_string("../i").unwrap();
+        let set = InstructionSet::extended().unwrap();
+        let program = assemble(&source, &set).unwrap();
+        assert_eq!(assemble(&disassemble(&program), &set).unwrap(), program);
+    }
+
+    #[test]
+    fn undefined_label() {
+        let error = assemble("jmp nowhere", &InstructionSet::default()).unwrap_err();
+        assert_eq!(error.to_string(), "Line 1: Label nowhere is not defined");
+    }
+}
@@ -0,0 +1,210 @@
+use instruction::Command;
+use machine::{Machine, State, StepOutcome};
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Breakpoint {
+    Address(usize),
+    Opcode(Command),
+}
+
+pub struct Debugger<'a> {
+    machine: Machine<'a>,
+    // state before every executed step, for stepping backwards
+    history: Vec<State>,
+    breakpoints: HashSet<Breakpoint>,
+    watch: bool,
+}
+
+const HELP: &str = "\
+commands:
+  s, step [n]          execute n instructions (default 1)
+  c, continue          run until a breakpoint or the program stops
+  b, back [n]          undo n instructions (default 1)
+  break <addr|opcode>  stop before an address or any jmp/nop/acc
+  delete <addr|opcode> remove a breakpoint
+  watch                toggle printing accumulator changes
+  l, list [n]          show n instructions either side of pc (default 3)
+  i, info              show pc, accumulator, step count and breakpoints
+  q, quit";
+
+impl<'a> Debugger<'a> {
+    pub fn new(machine: Machine<'a>) -> Debugger<'a> {
+        Debugger {
+            machine,
+            history: vec![],
+            breakpoints: HashSet::new(),
+            watch: false,
+        }
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}", HELP)?;
+        self.list(&mut out, 3)?;
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let count = |default| words.get(1).and_then(|n| n.parse().ok()).unwrap_or(default);
+            match words.first().cloned() {
+                Some("s") | Some("step") => {
+                    for _ in 0..count(1) {
+                        if !self.step(&mut out)? {
+                            break;
+                        }
+                    }
+                    self.list(&mut out, 0)?;
+                }
+                Some("c") | Some("continue") => {
+                    while self.step(&mut out)? && !self.at_breakpoint() {}
+                    self.list(&mut out, 0)?;
+                }
+                Some("b") | Some("back") => {
+                    for _ in 0..count(1) {
+                        match self.history.pop() {
+                            Some(state) => self.machine.rewind(state),
+                            None => {
+                                writeln!(out, "at the start of the recorded history")?;
+                                break;
+                            }
+                        }
+                    }
+                    self.list(&mut out, 0)?;
+                }
+                Some("break") | Some("delete") => match words.get(1).and_then(|w| breakpoint(w)) {
+                    Some(bp) if words[0] == "break" => {
+                        self.breakpoints.insert(bp);
+                    }
+                    Some(bp) => {
+                        self.breakpoints.remove(&bp);
+                    }
+                    None => writeln!(out, "expected an address or jmp, nop or acc")?,
+                },
+                Some("watch") => {
+                    self.watch = !self.watch;
+                    writeln!(out, "watch {}", if self.watch { "on" } else { "off" })?;
+                }
+                Some("l") | Some("list") => self.list(&mut out, count(3))?,
+                Some("i") | Some("info") => {
+                    let state = self.machine.state();
+                    writeln!(
+                        out,
+                        "pc {} acc {} steps {} breakpoints {:?}",
+                        state.pc, state.acc, state.steps, self.breakpoints
+                    )?;
+                }
+                Some("q") | Some("quit") => return Ok(()),
+                Some(_) => writeln!(out, "{}", HELP)?,
+                None => {}
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    // Returns whether the machine can keep going
+    fn step<W: Write>(&mut self, out: &mut W) -> io::Result<bool> {
+        let before = self.machine.state();
+        match self.machine.step() {
+            StepOutcome::Continued => {
+                self.history.push(before);
+                if self.watch && self.machine.acc != before.acc {
+                    writeln!(
+                        out,
+                        "acc {} -> {} at {}",
+                        before.acc, self.machine.acc, before.pc
+                    )?;
+                }
+                Ok(true)
+            }
+            outcome => {
+                writeln!(out, "stopped: {:?}", outcome)?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        let pc = self.machine.pc;
+        self.breakpoints.contains(&Breakpoint::Address(pc))
+            || self
+                .machine
+                .program()
+                .get(pc)
+                .is_some_and(|i| self.breakpoints.contains(&Breakpoint::Opcode(i.cmd)))
+    }
+
+    fn list<W: Write>(&self, out: &mut W, around: usize) -> io::Result<()> {
+        let pc = self.machine.pc;
+        let program = self.machine.program();
+        let end = (pc + around + 1).min(program.len());
+        for (address, instruction) in program
+            .iter()
+            .enumerate()
+            .take(end)
+            .skip(pc.saturating_sub(around))
+        {
+            writeln!(
+                out,
+                "{} {:>5}: {}",
+                if address == pc { "=>" } else { "  " },
+                address,
+                instruction
+            )?;
+        }
+        if pc >= program.len() {
+            writeln!(out, "=> {:>5}: <end>", pc)?;
+        }
+        writeln!(out, "acc = {}", self.machine.acc)
+    }
+}
+
+fn breakpoint(word: &str) -> Option<Breakpoint> {
+    word.parse()
+        .map(Breakpoint::Address)
+        .or_else(|_| word.parse().map(Breakpoint::Opcode))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instruction::Instruction;
+    use machine::EXAMPLE;
+
+    fn session(script: &str) -> String {
+        let program = EXAMPLE
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect::<Vec<Instruction>>();
+        let mut out = vec![];
+        Debugger::new(Machine::new(&program))
+            .run(script.as_bytes(), &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn breakpoint_watch_and_back() {
+        let out = session("watch\nbreak jmp\nc\ni\nb\ni\nb 5\nq\n");
+        assert!(out.contains("watch on\n"));
+        assert!(out.contains("acc 0 -> 1 at 1\n"));
+        // continue stops before the first jmp
+        assert!(out.contains("=>     2: jmp +4\nacc = 1\n"));
+        assert!(out.contains("pc 2 acc 1 steps 2 breakpoints {Opcode(Jmp)}\n"));
+        // back undoes the acc +1
+        assert!(out.contains("=>     1: acc +1\nacc = 0\n"));
+        assert!(out.contains("pc 1 acc 0 steps 1 breakpoints"));
+        assert!(out.contains("at the start of the recorded history\n"));
+    }
+
+    #[test]
+    fn continue_runs_until_the_loop() {
+        let out = session("break 99\ndelete 99\nc\ni\n");
+        assert!(out.contains("stopped: InfiniteLoop { pc: 1 }\n"));
+        assert!(out.contains("pc 1 acc 5 steps 7 breakpoints {}\n"));
+    }
+}
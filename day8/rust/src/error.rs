@@ -0,0 +1,46 @@
+use machine::StepOutcome;
+use snafu::Snafu;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Could not open file {}: {}", filename.display(), source))]
+    OpenFile {
+        filename: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Could not read line: {}", source))]
+    ReadLine { source: std::io::Error },
+    #[snafu(display("Could not talk to the terminal: {}", source))]
+    Terminal { source: std::io::Error },
+    #[snafu(display("Could not find any valid lines"))]
+    InvalidLine {},
+    #[snafu(display("Unknown opcode {}", name))]
+    UnknownOpcode { name: String },
+    #[snafu(display("Opcode {} is built in and cannot be replaced", name))]
+    ReservedOpcode { name: String },
+    #[snafu(display("Unknown register {}, expected acc or a to d", name))]
+    UnknownRegister { name: String },
+    #[snafu(display("Line {}: {}", line, source))]
+    Assembly { line: usize, source: Box<Error> },
+    #[snafu(display("Label {} is defined more than once", label))]
+    DuplicateLabel { label: String },
+    #[snafu(display("Label {} is not defined", label))]
+    UndefinedLabel { label: String },
+    #[snafu(display("Could not open parse int: {}", source))]
+    ParseInt { source: ParseIntError },
+    #[snafu(display("Could not serialize to JSON: {}", source))]
+    Json { source: serde_json::Error },
+    #[snafu(display("Unknown command {}", command))]
+    UnknownCommand { command: String },
+    #[snafu(display("Unknown or incomplete option {}", option))]
+    BadOption { option: String },
+    #[snafu(display("Program stopped with {:?}", outcome))]
+    UnexpectedOutcome { outcome: StepOutcome },
+    #[snafu(display("No single jmp/nop swap makes the program terminate"))]
+    NoRepair {},
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
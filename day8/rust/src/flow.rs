@@ -0,0 +1,314 @@
+use instruction::{Command, Instruction};
+use petgraph::algo::kosaraju_scc;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::{Bfs, EdgeRef, Reversed};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Node {
+    // the instructions start..end, entered only at start
+    Block { start: usize, end: usize },
+    // running off the end of the program
+    End,
+    // a jump before the start or more than one past the end
+    Outside,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Next,
+    Jump,
+}
+
+// Control-flow graph of basic blocks. Extended opcodes are assumed to fall
+// through unless they are jumps, which may either jump or fall through, or
+// halt, which leaves their block with no successor.
+pub struct Cfg<'a> {
+    program: &'a [Instruction],
+    graph: DiGraph<Node, Edge>,
+    // the block holding each instruction
+    blocks: Vec<NodeIndex>,
+    end: NodeIndex,
+}
+
+fn targets(address: usize, instruction: &Instruction) -> Vec<(i64, Edge)> {
+    let next = (address as i64 + 1, Edge::Next);
+    let jump = (address as i64 + i64::from(instruction.val), Edge::Jump);
+    match instruction.cmd {
+        Command::Jmp => vec![jump],
+        Command::Ext(signature) if signature.jump => vec![next, jump],
+        Command::Ext(signature) if signature.halts => vec![],
+        Command::Nop | Command::Acc | Command::Ext(_) => vec![next],
+    }
+}
+
+impl<'a> Cfg<'a> {
+    pub fn new(program: &'a [Instruction]) -> Cfg<'a> {
+        let len = program.len();
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (address, instruction) in program.iter().enumerate() {
+            let targets = targets(address, instruction);
+            // a halt ends its block like a jump
+            if targets.is_empty() {
+                leaders.insert(address + 1);
+            }
+            for (target, edge) in targets {
+                if edge == Edge::Jump {
+                    leaders.insert(address + 1);
+                    if target >= 0 && target < len as i64 {
+                        leaders.insert(target as usize);
+                    }
+                }
+            }
+        }
+        let mut graph = DiGraph::new();
+        let mut blocks = Vec::with_capacity(len);
+        let starts = leaders.into_iter().filter(|&a| a < len).collect::<Vec<_>>();
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).cloned().unwrap_or(len);
+            let node = graph.add_node(Node::Block { start, end });
+            blocks.extend((start..end).map(|_| node));
+        }
+        let end = graph.add_node(Node::End);
+        let outside = graph.add_node(Node::Outside);
+        for (i, &start) in starts.iter().enumerate() {
+            let last = starts.get(i + 1).cloned().unwrap_or(len) - 1;
+            for (target, edge) in targets(last, &program[last]) {
+                let to = match target {
+                    t if t < 0 || t > len as i64 => outside,
+                    t if t == len as i64 => end,
+                    t => blocks[t as usize],
+                };
+                graph.add_edge(blocks[start], to, edge);
+            }
+        }
+        Cfg {
+            program,
+            graph,
+            blocks,
+            end,
+        }
+    }
+
+    pub fn block_count(&self) -> usize {
+        // every node but End and Outside
+        self.graph.node_count() - 2
+    }
+
+    fn entry(&self) -> NodeIndex {
+        self.blocks.first().cloned().unwrap_or(self.end)
+    }
+
+    fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.graph.node_count()];
+        let mut bfs = Bfs::new(&self.graph, self.entry());
+        while let Some(node) = bfs.next(&self.graph) {
+            seen[node.index()] = true;
+        }
+        seen
+    }
+
+    // Nodes from which the end of the program can be reached
+    fn terminating(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.graph.node_count()];
+        let reversed = Reversed(&self.graph);
+        let mut bfs = Bfs::new(reversed, self.end);
+        while let Some(node) = bfs.next(reversed) {
+            seen[node.index()] = true;
+        }
+        seen
+    }
+
+    pub fn unreachable(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        (0..self.program.len())
+            .filter(|&address| !reachable[self.blocks[address].index()])
+            .collect()
+    }
+
+    // The addresses of each set of blocks that can cycle back to themselves
+    pub fn loops(&self) -> Vec<Vec<usize>> {
+        let mut loops = kosaraju_scc(&self.graph)
+            .into_iter()
+            .filter(|nodes| nodes.len() > 1 || self.graph.find_edge(nodes[0], nodes[0]).is_some())
+            .map(|nodes| {
+                let mut addresses = nodes
+                    .into_iter()
+                    .flat_map(|node| self.addresses(node))
+                    .collect::<Vec<_>>();
+                addresses.sort_unstable();
+                addresses
+            })
+            .collect::<Vec<_>>();
+        loops.sort();
+        loops
+    }
+
+    // Jumps to before the start or more than one past the end, with targets
+    pub fn escapes(&self) -> Vec<(usize, i64)> {
+        let len = self.program.len() as i64;
+        self.program
+            .iter()
+            .enumerate()
+            .flat_map(|(address, instruction)| {
+                targets(address, instruction)
+                    .into_iter()
+                    .filter(|&(target, _)| target < 0 || target > len)
+                    .map(move |(target, _)| (address, target))
+            })
+            .collect()
+    }
+
+    // Reachable jmp/nop instructions that cannot currently reach the end but
+    // whose flipped successor does, so flipping one makes the program stop
+    pub fn terminating_flips(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        let terminating = self.terminating();
+        let len = self.program.len() as i64;
+        (0..self.program.len())
+            .filter(|&address| {
+                let block = self.blocks[address].index();
+                reachable[block] && !terminating[block]
+            })
+            .filter(|&address| {
+                let instruction = self.program[address];
+                let target = match instruction.cmd {
+                    Command::Jmp => address as i64 + 1,
+                    Command::Nop => address as i64 + i64::from(instruction.val),
+                    Command::Acc | Command::Ext(_) => return false,
+                };
+                target == len
+                    || (target >= 0
+                        && target < len
+                        && terminating[self.blocks[target as usize].index()])
+            })
+            .collect()
+    }
+
+    fn addresses(&self, node: NodeIndex) -> std::ops::Range<usize> {
+        match self.graph[node] {
+            Node::Block { start, end } => start..end,
+            Node::End | Node::Outside => 0..0,
+        }
+    }
+
+    // Blocks are labelled with their instructions; unreachable ones are grey
+    // and those holding a terminating flip are highlighted
+    pub fn to_dot(&self) -> String {
+        let reachable = self.reachable();
+        let flips = self
+            .terminating_flips()
+            .into_iter()
+            .map(|address| self.blocks[address])
+            .collect::<BTreeSet<_>>();
+        let mut out =
+            String::from("digraph program {\n    node [shape=box, fontname=monospace];\n");
+        for node in self.graph.node_indices() {
+            if self.graph[node] == Node::Outside
+                && self.graph.neighbors_undirected(node).next().is_none()
+            {
+                continue;
+            }
+            let style = if flips.contains(&node) {
+                ", style=filled, fillcolor=gold"
+            } else if !reachable[node.index()] {
+                ", style=filled, fillcolor=grey"
+            } else {
+                ""
+            };
+            let label = match self.graph[node] {
+                Node::Block { .. } => self
+                    .addresses(node)
+                    .map(|address| format!("{}: {}\\l", address, self.program[address]))
+                    .collect::<String>(),
+                Node::End => String::from("end"),
+                Node::Outside => String::from("outside"),
+            };
+            out += &format!("    n{} [label=\"{}\"{}];\n", node.index(), label, style);
+        }
+        for edge in self.graph.edge_references() {
+            let style = match edge.weight() {
+                Edge::Next => "",
+                Edge::Jump => " [style=bold]",
+            };
+            out += &format!(
+                "    n{} -> n{}{};\n",
+                edge.source().index(),
+                edge.target().index(),
+                style
+            );
+        }
+        out + "}\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machine::EXAMPLE;
+    use opcodes::InstructionSet;
+
+    fn parse(source: &str) -> Vec<Instruction> {
+        let set = InstructionSet::extended().unwrap();
+        source
+            .lines()
+            .map(|line| set.parse(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn example_blocks_and_loops() {
+        let program = parse(EXAMPLE);
+        let cfg = Cfg::new(&program);
+        // split at 0, after each jmp and at each jump target
+        assert_eq!(cfg.block_count(), 6);
+        assert_eq!(
+            (0..program.len())
+                .map(|address| cfg.blocks[address].index())
+                .collect::<Vec<_>>(),
+            vec![0, 1, 1, 2, 2, 3, 4, 4, 5]
+        );
+        assert_eq!(cfg.unreachable(), vec![5, 8]);
+        assert_eq!(cfg.loops(), vec![vec![1, 2, 3, 4, 6, 7]]);
+        assert!(cfg.escapes().is_empty());
+        assert_eq!(cfg.terminating_flips(), vec![7]);
+    }
+
+    #[test]
+    fn escapes_and_self_loops() {
+        let program = parse("jmp +0\njmp -5\njmp +2\njz a +9");
+        let cfg = Cfg::new(&program);
+        assert_eq!(cfg.escapes(), vec![(1, -4), (3, 12)]);
+        assert_eq!(cfg.loops(), vec![vec![0]]);
+        assert_eq!(cfg.unreachable(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn halt_has_no_successor() {
+        let program = parse("hlt\njmp -1");
+        let cfg = Cfg::new(&program);
+        assert_eq!(cfg.block_count(), 2);
+        assert!(cfg.loops().is_empty());
+        assert_eq!(cfg.unreachable(), vec![1]);
+        assert!(cfg.terminating_flips().is_empty());
+        // a halt in the middle of a block splits it
+        let program = parse("acc +1\nhlt\nacc +2");
+        assert_eq!(Cfg::new(&program).block_count(), 2);
+    }
+
+    #[test]
+    fn dot_marks_flips_and_unreachable_blocks() {
+        let program = parse(EXAMPLE);
+        let dot = Cfg::new(&program).to_dot();
+        assert!(dot.starts_with("digraph program {\n"));
+        assert!(dot.contains(
+            "    n4 [label=\"6: acc +1\\l7: jmp -4\\l\", style=filled, fillcolor=gold];\n"
+        ));
+        assert!(dot.contains("    n3 [label=\"5: acc -99\\l\", style=filled, fillcolor=grey];\n"));
+        assert!(dot.contains("    n1 -> n4 [style=bold];\n"));
+        assert!(dot.contains("    n0 -> n1;\n"));
+        // nothing jumps outside the program
+        assert!(!dot.contains("outside"));
+    }
+}
@@ -0,0 +1,80 @@
+// Property-based fuzzing of the parser and the interpreter with arbitrary
+// input; run with PROPTEST_CASES=<n> for a longer session
+use instruction::{Command, Instruction, Register};
+use machine::{Machine, StepOutcome};
+use opcodes::InstructionSet;
+use proptest::prelude::*;
+use repair::repair;
+
+const NAMES: [&str; 11] = [
+    "jmp", "nop", "acc", "jz", "jnz", "set", "add", "mul", "inp", "out", "hlt",
+];
+
+fn instruction(set: &InstructionSet, name: usize, reg: usize, val: i32) -> Instruction {
+    let cmd = name_to_command(set, NAMES[name]);
+    Instruction {
+        cmd,
+        reg: Register::new(reg).unwrap(),
+        val,
+    }
+}
+
+fn name_to_command(set: &InstructionSet, name: &str) -> Command {
+    name.parse()
+        .unwrap_or_else(|_| Command::Ext(set.get(name).unwrap().signature()))
+}
+
+proptest! {
+    #[test]
+    fn from_str_never_panics(s in "\\PC*") {
+        let _ = s.parse::<Instruction>();
+        let _ = InstructionSet::extended().unwrap().parse(&s);
+    }
+
+    #[test]
+    fn parsed_instructions_display_back(
+        s in "(jmp|nop|acc|jz a|jnz d|set b|add c|mul acc) [+-]?[0-9]{1,11}"
+    ) {
+        let set = InstructionSet::extended().unwrap();
+        if let Ok(instruction) = set.parse(&s) {
+            prop_assert_eq!(set.parse(&instruction.to_string()).unwrap(), instruction);
+        }
+    }
+
+    #[test]
+    fn run_always_stops(
+        program in prop::collection::vec((0..NAMES.len(), 0..5usize, any::<i32>()), 0..64),
+        input in prop::collection::vec(any::<i32>(), 0..8),
+    ) {
+        let set = InstructionSet::extended().unwrap();
+        let program = program
+            .into_iter()
+            .map(|(name, reg, val)| instruction(&set, name, reg, val))
+            .collect::<Vec<_>>();
+        let mut machine = Machine::new(&program).with_instruction_set(&set);
+        machine.detect_loops(false);
+        machine.limit_steps(10_000);
+        machine.provide_input(input);
+        machine.run();
+        prop_assert!(machine.steps <= 10_000);
+        // each step writes at most one output
+        prop_assert!(machine.output().len() <= machine.steps);
+    }
+
+    #[test]
+    fn repair_is_sound(
+        program in prop::collection::vec((0..3usize, -8..8i32), 1..64),
+    ) {
+        let set = InstructionSet::default();
+        let program = program
+            .into_iter()
+            .map(|(name, val)| instruction(&set, name, 0, val))
+            .collect::<Vec<_>>();
+        if let Some(fixed) = repair(&program) {
+            let patched = ::repair::flip(&program, fixed.address).unwrap();
+            let mut machine = Machine::new(&patched);
+            prop_assert_eq!(machine.run(), StepOutcome::Terminated);
+            prop_assert_eq!(machine.acc, fixed.acc);
+        }
+    }
+}
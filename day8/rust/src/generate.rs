@@ -0,0 +1,201 @@
+use error::*;
+use instruction::{Command, Instruction};
+use machine::{Machine, StepOutcome};
+use std::ops::Range;
+
+// A looping program that one known jmp/nop flip repairs, with the answers
+// to both parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generated {
+    pub program: Vec<Instruction>,
+    pub corrupt: usize,
+    // the accumulator when the loop is detected, and after the repaired run
+    pub loop_acc: i32,
+    pub fixed_acc: i32,
+}
+
+// Deterministic xorshift so a seed always gives the same program
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+
+    fn value(&mut self) -> i32 {
+        self.next(101) as i32 - 50
+    }
+
+    // An offset from address to a random address in region
+    fn offset(&mut self, address: usize, region: &Range<usize>) -> i32 {
+        (region.start + self.next(region.len())) as i32 - address as i32
+    }
+}
+
+// The program is split into a looping region 0..tail and a terminating tail
+// tail..len. The repaired program runs a random path through the looping
+// region ending at its last address, a nop falling into the tail, then runs
+// every run of the tail in a random order to the end. The corrupt program
+// turns that nop into a jmp back into the looping region.
+//
+// Every other instruction in the looping region keeps both its successor
+// and its flipped successor inside the region, so once the corrupt program
+// enters it nothing there can reach the tail: it loops, and only flipping
+// the corrupt instruction back makes it terminate.
+pub fn generate(len: usize, seed: u64) -> Result<Generated> {
+    let len = len.max(1);
+    // xorshift never leaves a zero state
+    let mut rng = Rng((seed ^ 0x2545_f491_4f6c_dd1d).max(1));
+    let tail = 1 + len / 4 + rng.next(len / 2 + 1).min(len - 1 - len / 4);
+    let looping = 0..tail;
+    let corrupt = tail - 1;
+
+    let mut program = looping
+        .clone()
+        .map(|address| match rng.next(3) {
+            0 => Instruction::new(Command::Acc, rng.value()),
+            1 => Instruction::new(Command::Nop, rng.offset(address, &looping)),
+            _ => Instruction::new(Command::Jmp, rng.offset(address, &looping)),
+        })
+        .collect::<Vec<_>>();
+    let mut runs = split(looping.clone(), &mut rng);
+    let last = runs.pop().unwrap_or(corrupt..tail);
+    let mut path = if runs.is_empty() {
+        vec![]
+    } else {
+        vec![runs.remove(0)]
+    };
+    for _ in 0..runs.len().div_ceil(2) + rng.next(runs.len() / 2 + 1) {
+        path.push(runs.swap_remove(rng.next(runs.len())));
+    }
+    path.push(last);
+    lay_path(&mut program, &path, &looping, &mut rng);
+    program[corrupt] = Instruction::new(Command::Nop, rng.offset(corrupt, &looping));
+
+    // the tail runs start with the one the nop falls into
+    let mut runs = split(tail..len, &mut rng);
+    let mut path = vec![];
+    if !runs.is_empty() {
+        path.push(runs.remove(0));
+    }
+    while !runs.is_empty() {
+        path.push(runs.swap_remove(rng.next(runs.len())));
+    }
+    program.extend((tail..len).map(|_| Instruction::new(Command::Acc, 0)));
+    lay_path(&mut program, &path, &(0..len), &mut rng);
+
+    let fixed_acc = run(&program, true)?;
+    program[corrupt].cmd = Command::Jmp;
+    let loop_acc = run(&program, false)?;
+    Ok(Generated {
+        program,
+        corrupt,
+        loop_acc,
+        fixed_acc,
+    })
+}
+
+// Runs of up to eight consecutive addresses covering the range
+fn split(range: Range<usize>, rng: &mut Rng) -> Vec<Range<usize>> {
+    let mut runs = vec![];
+    let mut start = range.start;
+    while start < range.end {
+        let end = (start + 1 + rng.next(8)).min(range.end);
+        runs.push(start..end);
+        start = end;
+    }
+    runs
+}
+
+// Fills in the runs so they execute in order, each jumping to the next and
+// the last one leaving by its end address. Random nops point into region.
+fn lay_path(
+    program: &mut [Instruction],
+    runs: &[Range<usize>],
+    region: &Range<usize>,
+    rng: &mut Rng,
+) {
+    let addresses = runs.iter().cloned().flatten().collect::<Vec<_>>();
+    for (i, &address) in addresses.iter().enumerate() {
+        let next = addresses.get(i + 1).cloned().unwrap_or(address + 1);
+        program[address] = if next != address + 1 {
+            Instruction::new(Command::Jmp, next as i32 - address as i32)
+        } else {
+            match rng.next(4) {
+                0 | 1 => Instruction::new(Command::Acc, rng.value()),
+                2 => Instruction::new(Command::Nop, rng.offset(address, region)),
+                _ => Instruction::new(Command::Jmp, 1),
+            }
+        };
+    }
+    if let Some(run) = runs.last() {
+        let last = run.end - 1;
+        let end = region.end.max(run.end);
+        if last + 1 != end {
+            program[last] = Instruction::new(Command::Jmp, end as i32 - last as i32);
+        }
+    }
+}
+
+// The accumulator once the program terminates or loops, as expected
+fn run(program: &[Instruction], terminates: bool) -> Result<i32> {
+    let mut machine = Machine::new(program);
+    match machine.run() {
+        StepOutcome::Terminated if terminates => Ok(machine.acc),
+        StepOutcome::InfiniteLoop { .. } if !terminates => Ok(machine.acc),
+        outcome => UnexpectedOutcome { outcome }.fail(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flow::Cfg;
+    use repair::{repair, Repair};
+
+    #[test]
+    fn repair_finds_the_corrupt_instruction() {
+        for seed in 0..100 {
+            let generated = generate(1 + seed as usize, seed).unwrap();
+            assert_eq!(
+                repair(&generated.program),
+                Some(Repair {
+                    address: generated.corrupt,
+                    acc: generated.fixed_acc,
+                }),
+                "seed {}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_instruction_is_the_only_fix() {
+        for seed in 0..20 {
+            for &len in &[1, 2, 600, 20_000] {
+                let generated = generate(len, seed).unwrap();
+                let cfg = Cfg::new(&generated.program);
+                assert_eq!(cfg.terminating_flips(), vec![generated.corrupt]);
+            }
+        }
+    }
+
+    #[test]
+    fn answers_differ() {
+        let same = (0..100)
+            .filter(|&seed| {
+                let generated = generate(600, seed).unwrap();
+                generated.loop_acc == generated.fixed_acc
+            })
+            .count();
+        assert!(same < 10, "{} programs with equal answers", same);
+    }
+
+    #[test]
+    fn same_seed_same_program() {
+        assert_eq!(generate(50, 7).unwrap(), generate(50, 7).unwrap());
+    }
+}
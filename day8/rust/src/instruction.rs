@@ -0,0 +1,146 @@
+use error::*;
+use opcodes::InstructionSet;
+use snafu::ResultExt;
+use std::fmt;
+use std::str::FromStr;
+
+// How an extended opcode is written: its name and which operands follow it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Signature {
+    pub name: &'static str,
+    pub register: bool,
+    pub value: bool,
+    // the value is a relative jump offset
+    pub jump: bool,
+    // running it stops the program
+    pub halts: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Command {
+    Jmp,
+    Nop,
+    Acc,
+    // an opcode provided by an InstructionSet
+    Ext(Signature),
+}
+
+// Register 0 is the accumulator, the rest are named a, b, c and d
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Register(usize);
+
+pub const REGISTER_NAMES: [&str; 5] = ["acc", "a", "b", "c", "d"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub cmd: Command,
+    // only meaningful for extended opcodes whose signature asks for them
+    pub reg: Register,
+    pub val: i32,
+}
+
+impl Instruction {
+    // Whether the value is a jump offset; a nop's is only used once flipped
+    pub fn is_jump(&self) -> bool {
+        match self.cmd {
+            Command::Jmp => true,
+            Command::Ext(signature) => signature.jump,
+            Command::Nop | Command::Acc => false,
+        }
+    }
+
+    pub fn new(cmd: Command, val: i32) -> Instruction {
+        Instruction {
+            cmd,
+            reg: Register::default(),
+            val,
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Command> {
+        match s {
+            "jmp" => Ok(Command::Jmp),
+            "nop" => Ok(Command::Nop),
+            "acc" => Ok(Command::Acc),
+            _ => UnknownOpcode { name: s }.fail(),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Jmp => write!(f, "jmp"),
+            Command::Nop => write!(f, "nop"),
+            Command::Acc => write!(f, "acc"),
+            Command::Ext(signature) => write!(f, "{}", signature.name),
+        }
+    }
+}
+
+impl Register {
+    // None for an index without a register name
+    pub fn new(index: usize) -> Option<Register> {
+        if index < REGISTER_NAMES.len() {
+            Some(Register(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl FromStr for Register {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Register> {
+        REGISTER_NAMES
+            .iter()
+            .position(|&name| name == s)
+            .and_then(Register::new)
+            .ok_or(Error::UnknownRegister {
+                name: s.to_string(),
+            })
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", REGISTER_NAMES[self.0])
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.cmd {
+            Command::Ext(signature) => {
+                write!(f, "{}", signature.name)?;
+                if signature.register {
+                    write!(f, " {}", self.reg)?;
+                }
+                if signature.value {
+                    write!(f, " {:+}", self.val)?;
+                }
+                Ok(())
+            }
+            _ => write!(f, "{} {:+}", self.cmd, self.val),
+        }
+    }
+}
+
+// Parses the original jmp, nop and acc instructions only
+impl FromStr for Instruction {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Instruction> {
+        InstructionSet::default().parse(s)
+    }
+}
+
+pub fn parse_value(s: &str) -> Result<i32> {
+    s.parse().context(ParseInt {})
+}
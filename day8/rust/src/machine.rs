@@ -0,0 +1,418 @@
+use instruction::{Command, Instruction, Register};
+use opcodes::{Base, Effect, InstructionSet, Opcode};
+use serde::{Serialize, Serializer};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Continued,
+    // pc reached the address just past the last instruction
+    Terminated,
+    // a jump from pc to an address before the start of the program
+    BeforeStart { pc: usize, target: i64 },
+    // a jump from pc to beyond the address just past the last instruction
+    PastEnd { pc: usize, target: i64 },
+    // the instruction at pc would overflow the accumulator or a register
+    Overflow { pc: usize },
+    // the instruction at pc was about to run a second time
+    InfiniteLoop { pc: usize },
+    // a hlt at pc stopped the program
+    Halted { pc: usize },
+    // an inp at pc is waiting for more input
+    NeedInput { pc: usize },
+    // the extended opcode at pc is not in the machine's instruction set
+    UnknownOpcode { pc: usize },
+    // the step limit was reached before running the instruction at pc
+    StepLimit { pc: usize },
+    // the time limit ran out before running the instruction at pc
+    TimeLimit { pc: usize },
+}
+
+// Everything needed to rewind the machine to an earlier step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct State {
+    pub pc: usize,
+    pub acc: i32,
+    pub steps: usize,
+    pub registers: [i32; 4],
+    pub input_pos: usize,
+    pub outputs: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceEntry {
+    pub step: usize,
+    pub pc: usize,
+    #[serde(serialize_with = "as_string")]
+    pub instruction: Instruction,
+    pub acc_before: i32,
+    pub acc_after: i32,
+}
+
+fn as_string<S: Serializer>(instruction: &Instruction, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(instruction)
+}
+
+pub struct Machine<'a> {
+    program: &'a [Instruction],
+    pub pc: usize,
+    pub acc: i32,
+    pub steps: usize,
+    visited: Vec<bool>,
+    // only kept once recording has been turned on
+    trace: Option<Vec<TraceEntry>>,
+    // registers a to d; the accumulator doubles as register 0
+    registers: [i32; 4],
+    input: Vec<i32>,
+    input_pos: usize,
+    output: Vec<i32>,
+    instruction_set: Option<&'a InstructionSet>,
+    // programs with conditional jumps may revisit an address legitimately
+    detect_loops: bool,
+    max_steps: Option<usize>,
+    deadline: Option<Instant>,
+    // set once a hlt has run, so stepping again does not count it twice
+    halted: bool,
+    // times each instruction has run, once profiling has been turned on
+    profile: Option<Vec<usize>>,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(program: &'a [Instruction]) -> Machine<'a> {
+        Machine {
+            program,
+            pc: 0,
+            acc: 0,
+            steps: 0,
+            visited: vec![false; program.len()],
+            trace: None,
+            registers: [0; 4],
+            input: vec![],
+            input_pos: 0,
+            output: vec![],
+            instruction_set: None,
+            detect_loops: true,
+            max_steps: None,
+            deadline: None,
+            halted: false,
+            profile: None,
+        }
+    }
+
+    pub fn with_instruction_set(mut self, instruction_set: &'a InstructionSet) -> Machine<'a> {
+        self.instruction_set = Some(instruction_set);
+        self
+    }
+
+    pub fn detect_loops(&mut self, detect: bool) {
+        self.detect_loops = detect;
+    }
+
+    // Stops the machine once it has executed max steps in total
+    pub fn limit_steps(&mut self, max: usize) {
+        self.max_steps = Some(max);
+    }
+
+    // Stops the machine once the given time from now has passed
+    pub fn limit_time(&mut self, max: Duration) {
+        self.deadline = Some(Instant::now() + max);
+    }
+
+    pub fn record_profile(&mut self) {
+        let len = self.program.len();
+        self.profile.get_or_insert_with(|| vec![0; len]);
+    }
+
+    pub fn profile(&self) -> Option<&[usize]> {
+        self.profile.as_deref()
+    }
+
+    pub fn provide_input<I: IntoIterator<Item = i32>>(&mut self, values: I) {
+        self.input.extend(values);
+    }
+
+    pub fn output(&self) -> &[i32] {
+        &self.output
+    }
+
+    pub fn register(&self, register: Register) -> i32 {
+        match register.index() {
+            0 => self.acc,
+            n => self.registers[n - 1],
+        }
+    }
+
+    pub fn set_register(&mut self, register: Register, value: i32) {
+        match register.index() {
+            0 => self.acc = value,
+            n => self.registers[n - 1] = value,
+        }
+    }
+
+    pub fn read_input(&mut self) -> Option<i32> {
+        let value = *self.input.get(self.input_pos)?;
+        self.input_pos += 1;
+        Some(value)
+    }
+
+    pub fn write_output(&mut self, value: i32) {
+        self.output.push(value);
+    }
+
+    pub fn record_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    pub fn trace(&self) -> Option<&[TraceEntry]> {
+        self.trace.as_deref()
+    }
+
+    pub fn program(&self) -> &'a [Instruction] {
+        self.program
+    }
+
+    pub fn state(&self) -> State {
+        State {
+            pc: self.pc,
+            acc: self.acc,
+            steps: self.steps,
+            registers: self.registers,
+            input_pos: self.input_pos,
+            outputs: self.output.len(),
+        }
+    }
+
+    // Restores a state taken before the most recent steps, which must be
+    // undone latest first
+    pub fn rewind(&mut self, state: State) {
+        self.pc = state.pc;
+        self.acc = state.acc;
+        self.steps = state.steps;
+        self.registers = state.registers;
+        self.input_pos = state.input_pos;
+        self.output.truncate(state.outputs);
+        self.halted = false;
+        if let Some(visited) = self.visited.get_mut(state.pc) {
+            *visited = false;
+        }
+        if let Some(count) = self.profile.as_mut().and_then(|p| p.get_mut(state.pc)) {
+            *count = count.saturating_sub(1);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.retain(|entry| entry.step < state.steps);
+        }
+    }
+
+    // Executes the instruction at pc unless the machine has stopped
+    pub fn step(&mut self) -> StepOutcome {
+        let len = self.program.len();
+        let instruction = match self.program.get(self.pc) {
+            Some(&instruction) => instruction,
+            None if self.pc == len => return StepOutcome::Terminated,
+            None => {
+                return StepOutcome::PastEnd {
+                    pc: self.pc,
+                    target: self.pc as i64,
+                }
+            }
+        };
+        if self.halted {
+            return StepOutcome::Halted { pc: self.pc };
+        }
+        if self.detect_loops && self.visited[self.pc] {
+            return StepOutcome::InfiniteLoop { pc: self.pc };
+        }
+        if self.max_steps.is_some_and(|max| self.steps >= max) {
+            return StepOutcome::StepLimit { pc: self.pc };
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return StepOutcome::TimeLimit { pc: self.pc };
+        }
+        let acc_before = self.acc;
+        let effect = match instruction.cmd {
+            Command::Ext(signature) => {
+                let set = self.instruction_set;
+                match set.and_then(|set| set.get(signature.name)) {
+                    Some(opcode) => opcode.execute(&instruction, self),
+                    None => return StepOutcome::UnknownOpcode { pc: self.pc },
+                }
+            }
+            // the defaults run even without an instruction set
+            cmd => Base(cmd).execute(&instruction, self),
+        };
+        let next = match effect {
+            Effect::Next => self.pc as i64 + 1,
+            Effect::Jump(offset) => self.pc as i64 + i64::from(offset),
+            Effect::Halt => {
+                // the hlt itself ran, but pc stays on it
+                self.retire(instruction, acc_before);
+                self.halted = true;
+                return StepOutcome::Halted { pc: self.pc };
+            }
+            Effect::NeedInput => return StepOutcome::NeedInput { pc: self.pc },
+            Effect::Overflow => return StepOutcome::Overflow { pc: self.pc },
+        };
+        if next < 0 {
+            return StepOutcome::BeforeStart {
+                pc: self.pc,
+                target: next,
+            };
+        }
+        if next > len as i64 {
+            return StepOutcome::PastEnd {
+                pc: self.pc,
+                target: next,
+            };
+        }
+        self.retire(instruction, acc_before);
+        self.pc = next as usize;
+        StepOutcome::Continued
+    }
+
+    // Records that the instruction at pc has run
+    fn retire(&mut self, instruction: Instruction, acc_before: i32) {
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                step: self.steps,
+                pc: self.pc,
+                instruction,
+                acc_before,
+                acc_after: self.acc,
+            });
+        }
+        if let Some(profile) = &mut self.profile {
+            profile[self.pc] += 1;
+        }
+        self.visited[self.pc] = true;
+        self.steps += 1;
+    }
+
+    // Steps until the machine stops for any reason
+    pub fn run(&mut self) -> StepOutcome {
+        loop {
+            match self.step() {
+                StepOutcome::Continued => {}
+                outcome => return outcome,
+            }
+        }
+    }
+}
+
+// The example program from the puzzle
+#[cfg(test)]
+pub const EXAMPLE: &str = "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Instruction> {
+        source.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn example_loops_at_its_second_instruction() {
+        let program = parse(EXAMPLE);
+        let mut machine = Machine::new(&program);
+        assert_eq!(machine.run(), StepOutcome::InfiniteLoop { pc: 1 });
+        assert_eq!(machine.acc, 5);
+        assert_eq!(machine.steps, 7);
+        // stopped machines stay stopped
+        assert_eq!(machine.step(), StepOutcome::InfiniteLoop { pc: 1 });
+        assert_eq!(machine.steps, 7);
+    }
+
+    #[test]
+    fn step_by_step() {
+        let program = parse("acc +2\njmp +2\nacc +5\nacc -1");
+        let mut machine = Machine::new(&program);
+        assert_eq!(machine.step(), StepOutcome::Continued);
+        assert_eq!((machine.pc, machine.acc, machine.steps), (1, 2, 1));
+        assert_eq!(machine.step(), StepOutcome::Continued);
+        assert_eq!((machine.pc, machine.acc, machine.steps), (3, 2, 2));
+        assert_eq!(machine.step(), StepOutcome::Continued);
+        assert_eq!(machine.step(), StepOutcome::Terminated);
+        assert_eq!((machine.pc, machine.acc, machine.steps), (4, 1, 3));
+        assert_eq!(machine.step(), StepOutcome::Terminated);
+        assert_eq!(machine.steps, 3);
+    }
+
+    #[test]
+    fn jump_before_the_start() {
+        let program = parse("jmp -1");
+        let mut machine = Machine::new(&program);
+        assert_eq!(
+            machine.run(),
+            StepOutcome::BeforeStart { pc: 0, target: -1 }
+        );
+        assert_eq!(machine.steps, 0);
+    }
+
+    #[test]
+    fn jump_past_the_end() {
+        let program = parse("jmp +2");
+        let mut machine = Machine::new(&program);
+        assert_eq!(machine.run(), StepOutcome::PastEnd { pc: 0, target: 2 });
+    }
+
+    #[test]
+    fn jump_to_just_past_the_end_terminates() {
+        let program = parse("nop +0\njmp +1");
+        let mut machine = Machine::new(&program);
+        assert_eq!(machine.run(), StepOutcome::Terminated);
+        assert_eq!(machine.pc, 2);
+    }
+
+    #[test]
+    fn overflow_leaves_acc_unchanged() {
+        let program = parse("acc +2147483647\nacc +1");
+        let mut machine = Machine::new(&program);
+        assert_eq!(machine.run(), StepOutcome::Overflow { pc: 1 });
+        assert_eq!(machine.acc, i32::MAX);
+        assert_eq!(machine.steps, 1);
+    }
+
+    #[test]
+    fn step_limit_without_loop_detection() {
+        let program = parse("jmp +0");
+        let mut machine = Machine::new(&program);
+        machine.detect_loops(false);
+        machine.limit_steps(5);
+        machine.record_profile();
+        assert_eq!(machine.run(), StepOutcome::StepLimit { pc: 0 });
+        assert_eq!(machine.steps, 5);
+        assert_eq!(machine.profile(), Some(&[5][..]));
+    }
+
+    #[test]
+    fn time_limit() {
+        let program = parse("jmp +0");
+        let mut machine = Machine::new(&program);
+        machine.detect_loops(false);
+        machine.limit_time(Duration::from_millis(0));
+        assert_eq!(machine.run(), StepOutcome::TimeLimit { pc: 0 });
+        assert_eq!(machine.steps, 0);
+    }
+
+    #[test]
+    fn hlt_counts_once() {
+        let set = InstructionSet::extended().unwrap();
+        let program = vec![set.parse("hlt").unwrap()];
+        let mut machine = Machine::new(&program).with_instruction_set(&set);
+        machine.record_profile();
+        assert_eq!(machine.run(), StepOutcome::Halted { pc: 0 });
+        assert_eq!(machine.step(), StepOutcome::Halted { pc: 0 });
+        assert_eq!(machine.steps, 1);
+        assert_eq!(machine.profile(), Some(&[1][..]));
+    }
+
+    #[test]
+    fn empty_program_terminates() {
+        let mut machine = Machine::new(&[]);
+        assert_eq!(machine.run(), StepOutcome::Terminated);
+        assert_eq!(machine.steps, 0);
+    }
+}
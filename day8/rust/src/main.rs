@@ -1,123 +1,312 @@
 extern crate itertools;
 extern crate petgraph;
+#[cfg(test)]
+extern crate proptest;
+extern crate serde;
+extern crate serde_json;
 extern crate snafu;
 
-use snafu::{OptionExt, ResultExt, Snafu};
-use std::collections::HashSet;
-use std::fs::File;
+mod asm;
+mod debugger;
+mod error;
+mod flow;
+#[cfg(test)]
+mod fuzz;
+mod generate;
+mod instruction;
+mod machine;
+mod opcodes;
+mod profile;
+mod repair;
+mod trace;
+
+use debugger::Debugger;
+use error::*;
+use flow::Cfg;
+use instruction::{Command, Instruction};
+use machine::{Machine, StepOutcome};
+use opcodes::InstructionSet;
+use snafu::{OptionExt, ResultExt};
+use std::env;
+use std::fs::{self, File};
 use std::io::{self, BufRead};
-use std::num::ParseIntError;
-use std::path::{Path, PathBuf};
-use std::str::FromStr;
+use std::path::Path;
+use std::time::Duration;
+use trace::Format;
 
 fn main() {
-    println!("Part1: {:?}", part1());
-    println!("Part2: {:?}", part2());
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+    }
 }
 
-fn part1() -> Result<i32> {
-    run(read_lines("../i")?
-        .flatten()
-        .map(|s| s.parse())
-        .collect::<Result<Vec<_>>>()?)
-    .map(|(acc, _)| acc)
-    .context(InvalidLine {})
+fn run(args: &[String]) -> Result<()> {
+    match args.split_first() {
+        None => {
+            println!("Part1: {:?}", part1());
+            println!("Part2: {:?}", part2());
+            Ok(())
+        }
+        Some((command, rest)) => match command.as_str() {
+            "debug" => debug(rest.first().map_or("../i", String::as_str)),
+            "trace" => trace(rest),
+            "exec" => exec(rest),
+            "cfg" => cfg(rest),
+            "generate" => generate(rest),
+            "asm" => assemble(rest.first().map_or("../i", String::as_str)),
+            "disasm" => {
+                let filename = rest.first().map_or("../i", String::as_str);
+                let program = read_extended_program(filename, &InstructionSet::extended()?)?;
+                print!("{}", asm::disassemble(&program));
+                Ok(())
+            }
+            _ => UnknownCommand { command }.fail(),
+        },
+    }
 }
 
-fn part2() -> Result<i32> {
-    let instructions = read_lines("../i")?
-        .flatten()
-        .map(|s| s.parse())
-        .collect::<Result<Vec<_>>>()?;
-    (0..instructions.len())
-        .filter_map(|i| maybe_swap(&instructions, i))
-        .map(|is| run(is))
-        .flatten()
-        .filter(|(_, finished)| *finished)
-        .last()
-        .context(InvalidLine {})
-        .map(|(acc, _)| acc)
+// trace [--format text|csv|json] [--loop] [--flip <addr>] [file]
+fn trace(args: &[String]) -> Result<()> {
+    let mut format = Format::Text;
+    let mut loop_only = false;
+    let mut flip = None;
+    let mut filename = "../i";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = args.next().context(BadOption { option: arg })?.parse()?,
+            "--loop" => loop_only = true,
+            "--flip" => {
+                flip = Some(
+                    args.next()
+                        .context(BadOption { option: arg })?
+                        .parse::<usize>()
+                        .context(ParseInt {})?,
+                )
+            }
+            _ => filename = arg,
+        }
+    }
+    let mut instructions = read_program(filename)?;
+    if let Some(address) = flip {
+        instructions = repair::flip(&instructions, address).context(BadOption {
+            option: format!("--flip {}", address),
+        })?;
+    }
+    let mut machine = Machine::new(&instructions);
+    machine.record_trace();
+    let outcome = machine.run();
+    let entries = machine.trace().unwrap_or_default();
+    let entries = if loop_only {
+        trace::loop_only(entries, outcome)
+    } else {
+        entries
+    };
+    print!("{}", trace::export(entries, format)?);
+    eprintln!("{:?}", outcome);
+    Ok(())
 }
 
-fn maybe_swap(instructions: &Vec<Instruction>, i: usize) -> Option<Vec<Instruction>> {
-    match instructions.get(i)? {
-        Instruction {
-            cmd: Command::Nop,
-            val,
-        } => {
-            let mut new = instructions.to_vec();
-            new[i] = Instruction {
-                cmd: Command::Jmp,
-                val: *val,
-            };
-            Some(new)
-        }
-        Instruction {
-            cmd: Command::Jmp,
-            val,
-        } => {
-            let mut new = instructions.to_vec();
-            new[i] = Instruction {
-                cmd: Command::Nop,
-                val: *val,
-            };
-            Some(new)
+// Steps allowed when nothing else stops a program that may loop forever
+const DEFAULT_MAX_STEPS: usize = 10_000_000;
+
+// exec [--input 1,2,3] [--loop-check|--no-loop-check] [--max-steps n]
+//      [--max-time ms] [--profile [top]] [file]
+// Runs a program that may use the extended instruction set. Revisiting an
+// address only proves a loop for programs without extended opcodes, so only
+// those check for loops by default; otherwise a step limit applies.
+fn exec(args: &[String]) -> Result<()> {
+    let mut input = vec![];
+    let mut detect_loops = None;
+    let mut max_steps = None;
+    let mut max_time = None;
+    let mut profile = None;
+    let mut filename = "../i";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                input = args
+                    .next()
+                    .context(BadOption { option: arg })?
+                    .split(',')
+                    .map(|value| value.trim().parse().context(ParseInt {}))
+                    .collect::<Result<_>>()?
+            }
+            "--loop-check" => detect_loops = Some(true),
+            "--no-loop-check" => detect_loops = Some(false),
+            "--max-steps" => max_steps = Some(number(args.next(), arg)?),
+            "--max-time" => max_time = Some(Duration::from_millis(number(args.next(), arg)?)),
+            "--profile" => {
+                // the number of hottest addresses to show is optional
+                let top = args.as_slice().first().and_then(|n| n.parse().ok());
+                if top.is_some() {
+                    args.next();
+                }
+                profile = Some(top.unwrap_or(10));
+            }
+            _ => filename = arg,
         }
-        Instruction {
-            cmd: Command::Acc,
-            val: _,
-        } => None,
     }
+    let set = InstructionSet::extended()?;
+    let instructions = read_extended_program(filename, &set)?;
+    let mut machine = Machine::new(&instructions).with_instruction_set(&set);
+    let detect_loops = detect_loops.unwrap_or_else(|| {
+        instructions
+            .iter()
+            .all(|instruction| !matches!(instruction.cmd, Command::Ext(_)))
+    });
+    machine.detect_loops(detect_loops);
+    machine.provide_input(input);
+    match max_steps {
+        Some(max) => machine.limit_steps(max),
+        None if !detect_loops => machine.limit_steps(DEFAULT_MAX_STEPS),
+        None => {}
+    }
+    if let Some(max) = max_time {
+        machine.limit_time(max);
+    }
+    if profile.is_some() {
+        machine.record_profile();
+    }
+    let outcome = machine.run();
+    for value in machine.output() {
+        println!("{}", value);
+    }
+    eprintln!("{:?} acc {}", outcome, machine.acc);
+    if let (Some(top), Some(counts)) = (profile, machine.profile()) {
+        eprint!("{}", profile::report(&instructions, counts, top));
+    }
+    Ok(())
+}
+
+fn number<T: std::str::FromStr<Err = std::num::ParseIntError>>(
+    value: Option<&String>,
+    option: &str,
+) -> Result<T> {
+    value
+        .context(BadOption { option })?
+        .parse()
+        .context(ParseInt {})
+}
+
+// generate [length] [seed]
+// Prints a random corrupted program, and its expected answers to stderr
+fn generate(args: &[String]) -> Result<()> {
+    let len = args
+        .first()
+        .map_or(Ok(600), |n| n.parse().context(ParseInt {}))?;
+    let seed = args
+        .get(1)
+        .map_or(Ok(1), |n| n.parse().context(ParseInt {}))?;
+    let generated = generate::generate(len, seed)?;
+    for instruction in &generated.program {
+        println!("{}", instruction);
+    }
+    eprintln!(
+        "Corrupt: {} Part1: {} Part2: {}",
+        generated.corrupt, generated.loop_acc, generated.fixed_acc
+    );
+    Ok(())
+}
+
+// cfg [--dot] [file]
+fn cfg(args: &[String]) -> Result<()> {
+    let dot = args.iter().any(|arg| arg == "--dot");
+    let filename = args
+        .iter()
+        .find(|arg| *arg != "--dot")
+        .map_or("../i", String::as_str);
+    let instructions = read_extended_program(filename, &InstructionSet::extended()?)?;
+    let cfg = Cfg::new(&instructions);
+    if dot {
+        print!("{}", cfg.to_dot());
+        return Ok(());
+    }
+    println!("Blocks: {}", cfg.block_count());
+    println!("Unreachable: {}", ranges(&cfg.unreachable()));
+    for addresses in cfg.loops() {
+        println!("Loop: {}", ranges(&addresses));
+    }
+    for (address, target) in cfg.escapes() {
+        println!("Escape: {} jumps to {}", address, target);
+    }
+    println!("Terminating flips: {:?}", cfg.terminating_flips());
+    Ok(())
 }
 
-fn run(instructions: Vec<Instruction>) -> Option<(i32, bool)> {
-    let mut pc = 0;
-    let mut acc = 0;
-    let mut visited = HashSet::new();
-    while !visited.contains(&pc) {
-        visited.insert(pc);
-        if pc >= instructions.len() {
-            return Some((acc, true));
+// 1, 2, 3, 5 => "1-3, 5"
+fn ranges(addresses: &[usize]) -> String {
+    if addresses.is_empty() {
+        return String::from("none");
+    }
+    let mut out = Vec::<String>::new();
+    let mut i = 0;
+    while i < addresses.len() {
+        let mut j = i;
+        while j + 1 < addresses.len() && addresses[j + 1] == addresses[j] + 1 {
+            j += 1;
         }
-        match instructions.get(pc)? {
-            Instruction {
-                cmd: Command::Nop,
-                val: _,
-            } => {
-                pc = pc + 1;
-            }
-            Instruction {
-                cmd: Command::Jmp,
-                val,
-            } => {
-                pc = ((pc as i32) + val) as usize;
-            }
-            Instruction {
-                cmd: Command::Acc,
-                val,
-            } => {
-                pc = pc + 1;
-                acc = acc + val
-            }
-        };
+        out.push(if i == j {
+            addresses[i].to_string()
+        } else {
+            format!("{}-{}", addresses[i], addresses[j])
+        });
+        i = j + 1;
     }
-    Some((acc, false))
+    out.join(", ")
+}
+
+// Prints labelled source in the plain one-instruction-per-line format
+fn assemble(filename: &str) -> Result<()> {
+    let source = fs::read_to_string(filename).context(OpenFile { filename })?;
+    for instruction in asm::assemble(&source, &InstructionSet::extended()?)? {
+        println!("{}", instruction);
+    }
+    Ok(())
+}
+
+fn debug(filename: &str) -> Result<()> {
+    let instructions = read_program(filename)?;
+    let stdin = io::stdin();
+    Debugger::new(Machine::new(&instructions))
+        .run(stdin.lock(), io::stdout())
+        .context(Terminal {})
+}
+
+fn part1() -> Result<i32> {
+    let instructions = read_program("../i")?;
+    let mut machine = Machine::new(&instructions);
+    match machine.run() {
+        StepOutcome::InfiniteLoop { .. } => Ok(machine.acc),
+        outcome => UnexpectedOutcome { outcome }.fail(),
+    }
+}
+
+fn part2() -> Result<i32> {
+    Ok(repair::repair(&read_program("../i")?)
+        .context(NoRepair {})?
+        .acc)
 }
 
-#[derive(Debug, Snafu)]
-enum Error {
-    #[snafu(display("Could not open file {}: {}", filename.display(), source))]
-    OpenFile {
-        filename: PathBuf,
-        source: std::io::Error,
-    },
-    #[snafu(display("Could not find any valid lines"))]
-    InvalidLine {},
-    #[snafu(display("Could not open parse int: {}", source))]
-    ParseInt { source: ParseIntError },
+fn read_program<P>(filename: P) -> Result<Vec<Instruction>>
+where
+    P: AsRef<Path>,
+{
+    read_lines(filename)?
+        .map(|line| line.context(ReadLine {})?.parse())
+        .collect()
 }
 
-type Result<T, E = Error> = std::result::Result<T, E>;
+fn read_extended_program<P>(filename: P, set: &InstructionSet) -> Result<Vec<Instruction>>
+where
+    P: AsRef<Path>,
+{
+    read_lines(filename)?
+        .map(|line| set.parse(&line.context(ReadLine {})?))
+        .collect()
+}
 
 // The output is wrapped in a Result to allow matching on errors
 // Returns an Iterator to the Reader of the lines of the file.
@@ -130,37 +319,3 @@ where
     })?;
     Ok(io::BufReader::new(file).lines())
 }
-
-#[derive(Debug, Clone)]
-enum Command {
-    Jmp,
-    Nop,
-    Acc,
-}
-
-#[derive(Debug, Clone)]
-struct Instruction {
-    cmd: Command,
-    val: i32,
-}
-
-impl FromStr for Instruction {
-    type Err = Error;
-    fn from_str(s: &str) -> Result<Instruction> {
-        let parts = s.split(' ').collect::<Vec<_>>();
-        let cmd = (match parts.first().context(InvalidLine {})? {
-            &"jmp" => Some(Command::Jmp),
-            &"nop" => Some(Command::Nop),
-            &"acc" => Some(Command::Acc),
-            _ => None,
-        })
-        .context(InvalidLine {})?;
-        let val = parts
-            .last()
-            .context(InvalidLine {})?
-            .parse()
-            .context(ParseInt {})?;
-
-        Ok(Instruction { cmd, val })
-    }
-}
@@ -0,0 +1,297 @@
+use error::*;
+use instruction::{parse_value, Command, Instruction, Signature};
+use machine::Machine;
+use snafu::{ensure, OptionExt};
+use std::collections::HashMap;
+
+// What the machine should do after an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Next,
+    // relative to the current instruction, like jmp
+    Jump(i32),
+    Halt,
+    // an inp found no input left; the instruction has not run
+    NeedInput,
+    // the result did not fit in a register; the instruction has not run
+    Overflow,
+}
+
+pub trait Opcode {
+    fn signature(&self) -> Signature;
+    fn execute(&self, instruction: &Instruction, machine: &mut Machine) -> Effect;
+}
+
+// The opcodes a program may use, by name
+pub struct InstructionSet {
+    opcodes: HashMap<&'static str, Box<dyn Opcode>>,
+}
+
+// Just jmp, nop and acc
+impl Default for InstructionSet {
+    fn default() -> InstructionSet {
+        let mut set = InstructionSet {
+            opcodes: HashMap::new(),
+        };
+        for &cmd in &[Command::Jmp, Command::Nop, Command::Acc] {
+            set.insert(Box::new(Base(cmd)));
+        }
+        set
+    }
+}
+
+impl InstructionSet {
+    // The defaults plus registers, conditional jumps, arithmetic,
+    // input/output and halt
+    pub fn extended() -> Result<InstructionSet> {
+        let mut set = InstructionSet::default();
+        set.register(Box::new(JumpIf {
+            name: "jz",
+            zero: true,
+        }))?;
+        set.register(Box::new(JumpIf {
+            name: "jnz",
+            zero: false,
+        }))?;
+        set.register(Box::new(Arithmetic {
+            name: "set",
+            apply: |_, val| Some(val),
+        }))?;
+        set.register(Box::new(Arithmetic {
+            name: "add",
+            apply: i32::checked_add,
+        }))?;
+        set.register(Box::new(Arithmetic {
+            name: "mul",
+            apply: i32::checked_mul,
+        }))?;
+        set.register(Box::new(Input))?;
+        set.register(Box::new(Output))?;
+        set.register(Box::new(Halt))?;
+        Ok(set)
+    }
+
+    // Adds or replaces an opcode. jmp, nop and acc have their own commands,
+    // which repair and flow analysis rely on, so they cannot be replaced.
+    pub fn register(&mut self, opcode: Box<dyn Opcode>) -> Result<()> {
+        let name = opcode.signature().name;
+        ensure!(name.parse::<Command>().is_err(), ReservedOpcode { name });
+        self.insert(opcode);
+        Ok(())
+    }
+
+    fn insert(&mut self, opcode: Box<dyn Opcode>) {
+        self.opcodes.insert(opcode.signature().name, opcode);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Opcode> {
+        self.opcodes.get(name).map(|opcode| opcode.as_ref())
+    }
+
+    pub fn parse(&self, line: &str) -> Result<Instruction> {
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+        let name = *parts.first().context(InvalidLine {})?;
+        let signature = self.get(name).context(UnknownOpcode { name })?.signature();
+        let cmd = name.parse().unwrap_or(Command::Ext(signature));
+        let mut operands = parts[1..].iter();
+        let mut instruction = Instruction::new(cmd, 0);
+        if signature.register {
+            instruction.reg = operands.next().context(InvalidLine {})?.parse()?;
+        }
+        if signature.value {
+            instruction.val = parse_value(operands.next().context(InvalidLine {})?)?;
+        }
+        if operands.next().is_some() {
+            return InvalidLine {}.fail();
+        }
+        Ok(instruction)
+    }
+}
+
+// jmp, nop or acc
+pub struct Base(pub Command);
+
+impl Opcode for Base {
+    fn signature(&self) -> Signature {
+        Signature {
+            name: match self.0 {
+                Command::Jmp => "jmp",
+                Command::Nop => "nop",
+                Command::Acc => "acc",
+                Command::Ext(signature) => signature.name,
+            },
+            register: false,
+            value: true,
+            jump: self.0 == Command::Jmp,
+            halts: false,
+        }
+    }
+
+    fn execute(&self, instruction: &Instruction, machine: &mut Machine) -> Effect {
+        match self.0 {
+            Command::Jmp => Effect::Jump(instruction.val),
+            Command::Acc => match machine.acc.checked_add(instruction.val) {
+                Some(acc) => {
+                    machine.acc = acc;
+                    Effect::Next
+                }
+                None => Effect::Overflow,
+            },
+            Command::Nop | Command::Ext(_) => Effect::Next,
+        }
+    }
+}
+
+struct JumpIf {
+    name: &'static str,
+    zero: bool,
+}
+
+impl Opcode for JumpIf {
+    fn signature(&self) -> Signature {
+        Signature {
+            name: self.name,
+            register: true,
+            value: true,
+            jump: true,
+            halts: false,
+        }
+    }
+
+    fn execute(&self, instruction: &Instruction, machine: &mut Machine) -> Effect {
+        if (machine.register(instruction.reg) == 0) == self.zero {
+            Effect::Jump(instruction.val)
+        } else {
+            Effect::Next
+        }
+    }
+}
+
+struct Arithmetic {
+    name: &'static str,
+    // None on overflow
+    apply: fn(i32, i32) -> Option<i32>,
+}
+
+impl Opcode for Arithmetic {
+    fn signature(&self) -> Signature {
+        Signature {
+            name: self.name,
+            register: true,
+            value: true,
+            jump: false,
+            halts: false,
+        }
+    }
+
+    fn execute(&self, instruction: &Instruction, machine: &mut Machine) -> Effect {
+        match (self.apply)(machine.register(instruction.reg), instruction.val) {
+            Some(value) => {
+                machine.set_register(instruction.reg, value);
+                Effect::Next
+            }
+            None => Effect::Overflow,
+        }
+    }
+}
+
+struct Input;
+
+impl Opcode for Input {
+    fn signature(&self) -> Signature {
+        Signature {
+            name: "inp",
+            register: true,
+            value: false,
+            jump: false,
+            halts: false,
+        }
+    }
+
+    fn execute(&self, instruction: &Instruction, machine: &mut Machine) -> Effect {
+        match machine.read_input() {
+            Some(value) => {
+                machine.set_register(instruction.reg, value);
+                Effect::Next
+            }
+            None => Effect::NeedInput,
+        }
+    }
+}
+
+struct Output;
+
+impl Opcode for Output {
+    fn signature(&self) -> Signature {
+        Signature {
+            name: "out",
+            register: true,
+            value: false,
+            jump: false,
+            halts: false,
+        }
+    }
+
+    fn execute(&self, instruction: &Instruction, machine: &mut Machine) -> Effect {
+        let value = machine.register(instruction.reg);
+        machine.write_output(value);
+        Effect::Next
+    }
+}
+
+struct Halt;
+
+impl Opcode for Halt {
+    fn signature(&self) -> Signature {
+        Signature {
+            name: "hlt",
+            register: false,
+            value: false,
+            jump: false,
+            halts: true,
+        }
+    }
+
+    fn execute(&self, _: &Instruction, _: &mut Machine) -> Effect {
+        Effect::Halt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instruction::Register;
+
+    #[test]
+    fn default_set_is_the_original_three() {
+        let set = InstructionSet::default();
+        for name in &["jmp", "nop", "acc"] {
+            assert_eq!(set.get(name).unwrap().signature().name, *name);
+        }
+        assert!(set.get("jnz").is_none());
+        assert_eq!(
+            set.parse("acc -7").unwrap(),
+            Instruction::new(Command::Acc, -7)
+        );
+    }
+
+    #[test]
+    fn registers_are_bounded() {
+        assert_eq!(Register::new(4).map(Register::index), Some(4));
+        assert_eq!(Register::new(5), None);
+        let set = InstructionSet::extended().unwrap();
+        assert_eq!(set.parse("set d 1").unwrap().reg, Register::new(4).unwrap());
+        assert!(set.parse("set e 1").is_err());
+    }
+
+    #[test]
+    fn built_in_opcodes_cannot_be_replaced() {
+        let mut set = InstructionSet::extended().unwrap();
+        let error = set.register(Box::new(Base(Command::Jmp))).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Opcode jmp is built in and cannot be replaced"
+        );
+        assert!(set.register(Box::new(Halt)).is_ok());
+    }
+}
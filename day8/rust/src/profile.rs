@@ -0,0 +1,67 @@
+use instruction::Instruction;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Addresses by execution count, most executed first, ties by address
+pub fn hottest(counts: &[usize], top: usize) -> Vec<(usize, usize)> {
+    let mut hot = counts
+        .iter()
+        .cloned()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .collect::<Vec<_>>();
+    hot.sort_by_key(|&(address, count)| (std::cmp::Reverse(count), address));
+    hot.truncate(top);
+    hot
+}
+
+pub fn report(program: &[Instruction], counts: &[usize], top: usize) -> String {
+    let total = counts.iter().sum::<usize>();
+    let mut out = String::new();
+    writeln!(out, "{} steps", total).unwrap();
+    let mut opcodes = BTreeMap::new();
+    for (instruction, &count) in program.iter().zip(counts) {
+        *opcodes.entry(instruction.cmd.to_string()).or_insert(0) += count;
+    }
+    for (opcode, count) in opcodes {
+        writeln!(out, "{:>6} {:>10}", opcode, count).unwrap();
+    }
+    writeln!(out, "hottest addresses:").unwrap();
+    for (address, count) in hottest(counts, top) {
+        writeln!(
+            out,
+            "{:>6} {:>10} {:>5.1}%  {}",
+            address,
+            count,
+            100.0 * count as f64 / total as f64,
+            program[address]
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hottest_breaks_ties_by_address() {
+        let counts = [3, 5, 0, 5, 1];
+        assert_eq!(hottest(&counts, 3), vec![(1, 5), (3, 5), (0, 3)]);
+        // addresses that never ran are left out
+        assert_eq!(hottest(&counts, 10).len(), 4);
+    }
+
+    #[test]
+    fn report_counts_opcodes() {
+        let program = ["nop +0", "acc +1", "jmp -2"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect::<Vec<Instruction>>();
+        let report = report(&program, &[2, 2, 1], 1);
+        assert!(report.starts_with("5 steps\n"));
+        assert!(report.contains("   acc          2\n"));
+        assert!(report.contains("hottest addresses:\n     0          2  40.0%  nop +0\n"));
+    }
+}
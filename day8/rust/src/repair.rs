@@ -0,0 +1,104 @@
+use flow::Cfg;
+use instruction::{Command, Instruction};
+use machine::{Machine, StepOutcome};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    pub address: usize,
+    pub acc: i32,
+}
+
+// The program with the jmp/nop at address swapped, or None for anything else
+pub fn flip(program: &[Instruction], address: usize) -> Option<Vec<Instruction>> {
+    let cmd = match program.get(address)?.cmd {
+        Command::Nop => Command::Jmp,
+        Command::Jmp => Command::Nop,
+        Command::Acc | Command::Ext(_) => return None,
+    };
+    let mut flipped = program.to_vec();
+    flipped[address].cmd = cmd;
+    Some(flipped)
+}
+
+// Finds the single jmp/nop flip that makes a looping program terminate in
+// linear time. The flow graph supplies the reachable instructions whose
+// flipped successor can reach the end, and the first is confirmed by
+// running the patched program.
+pub fn repair(program: &[Instruction]) -> Option<Repair> {
+    let address = *Cfg::new(program).terminating_flips().first()?;
+    let patched = flip(program, address)?;
+    let mut machine = Machine::new(&patched);
+    match machine.run() {
+        StepOutcome::Terminated => Some(Repair {
+            address,
+            acc: machine.acc,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use read_program;
+
+    // Tries every flip from scratch, kept to check repair() against
+    fn brute_force(program: &[Instruction]) -> Vec<Repair> {
+        (0..program.len())
+            .filter_map(|address| {
+                let patched = flip(program, address)?;
+                let mut machine = Machine::new(&patched);
+                match machine.run() {
+                    StepOutcome::Terminated => Some(Repair {
+                        address,
+                        acc: machine.acc,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn parse(source: &str) -> Vec<Instruction> {
+        source.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn example() {
+        let program =
+            parse("nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6");
+        assert_eq!(repair(&program), Some(Repair { address: 7, acc: 8 }));
+        assert_eq!(brute_force(&program), vec![Repair { address: 7, acc: 8 }]);
+    }
+
+    #[test]
+    fn matches_brute_force_on_input() {
+        let program = read_program("../i").unwrap();
+        assert_eq!(
+            repair(&program).into_iter().collect::<Vec<_>>(),
+            brute_force(&program)
+        );
+    }
+
+    #[test]
+    fn matches_brute_force_on_every_single_corruption() {
+        // flipping any instruction of a terminating program and repairing
+        // it again must agree with the brute force search
+        let program = parse("nop +2\njmp +2\nacc +5\nacc -1\njmp +2\nnop -4\nacc +3");
+        for address in 0..program.len() {
+            if let Some(corrupt) = flip(&program, address) {
+                let expected = brute_force(&corrupt);
+                if expected.len() == 1 {
+                    assert_eq!(repair(&corrupt), Some(expected[0]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_repair_for_unfixable_program() {
+        let program = parse("jmp +0\njmp +0");
+        assert_eq!(repair(&program), None);
+        assert!(brute_force(&program).is_empty());
+    }
+}
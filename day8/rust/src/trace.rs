@@ -0,0 +1,125 @@
+use error::*;
+use machine::{StepOutcome, TraceEntry};
+use snafu::ResultExt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Format> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => BadOption { option: s }.fail(),
+        }
+    }
+}
+
+// The entries from the first run of the repeated instruction onwards, or
+// nothing if the program did not loop
+pub fn loop_only(trace: &[TraceEntry], outcome: StepOutcome) -> &[TraceEntry] {
+    match outcome {
+        StepOutcome::InfiniteLoop { pc } => {
+            let start = trace
+                .iter()
+                .rposition(|entry| entry.pc == pc)
+                .unwrap_or(trace.len());
+            &trace[start..]
+        }
+        _ => &[],
+    }
+}
+
+pub fn export(trace: &[TraceEntry], format: Format) -> Result<String> {
+    match format {
+        Format::Text => Ok(trace
+            .iter()
+            .map(|e| {
+                format!(
+                    "{:>6} {:>5}: {:<10} acc {} -> {}\n",
+                    e.step,
+                    e.pc,
+                    e.instruction.to_string(),
+                    e.acc_before,
+                    e.acc_after
+                )
+            })
+            .collect()),
+        Format::Csv => Ok(std::iter::once(
+            "step,pc,instruction,acc_before,acc_after\n".to_string(),
+        )
+        .chain(trace.iter().map(|e| {
+            format!(
+                "{},{},{},{},{}\n",
+                e.step, e.pc, e.instruction, e.acc_before, e.acc_after
+            )
+        }))
+        .collect()),
+        Format::Json => Ok(serde_json::to_string_pretty(trace).context(Json {})? + "\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instruction::Instruction;
+    use machine::{Machine, EXAMPLE};
+
+    fn example_trace() -> (Vec<TraceEntry>, StepOutcome) {
+        let program = EXAMPLE
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect::<Vec<Instruction>>();
+        let mut machine = Machine::new(&program);
+        machine.record_trace();
+        let outcome = machine.run();
+        (machine.trace().unwrap().to_vec(), outcome)
+    }
+
+    #[test]
+    fn loop_starts_at_the_repeated_pc() {
+        let (trace, outcome) = example_trace();
+        let pcs = loop_only(&trace, outcome)
+            .iter()
+            .map(|entry| entry.pc)
+            .collect::<Vec<_>>();
+        assert_eq!(pcs, vec![1, 2, 6, 7, 3, 4]);
+        assert!(loop_only(&trace, StepOutcome::Terminated).is_empty());
+    }
+
+    #[test]
+    fn exports() {
+        let (trace, _) = example_trace();
+        let csv = export(&trace, Format::Csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "step,pc,instruction,acc_before,acc_after");
+        assert_eq!(lines.len(), 1 + 7);
+        assert_eq!(lines[2], "1,1,acc +1,0,1");
+        let text = export(&trace, Format::Text).unwrap();
+        assert_eq!(text.lines().count(), 7);
+        assert_eq!(
+            text.lines().nth(1),
+            Some("     1     1: acc +1     acc 0 -> 1")
+        );
+        let json = export(&trace, Format::Json).unwrap();
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(json.as_array().map(Vec::len), Some(7));
+        assert_eq!(
+            json[1],
+            serde_json::json!({
+                "step": 1,
+                "pc": 1,
+                "instruction": "acc +1",
+                "acc_before": 0,
+                "acc_after": 1,
+            })
+        );
+    }
+}